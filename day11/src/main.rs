@@ -79,17 +79,13 @@ impl Automata {
         for y in 0..self.layout.len() - 2 {
             for x in 0..self.layout[0].len() - 2 {
                 match self.get_cell(x, y) {
-                    Cell::Empty => {
-                        if self.count_neighbors(x, y, &Cell::Taken) == 0 {
-                            modified = true;
-                            self.next_buf[y + 1][x + 1] = Cell::Taken
-                        }
+                    Cell::Empty if self.count_neighbors(x, y, &Cell::Taken) == 0 => {
+                        modified = true;
+                        self.next_buf[y + 1][x + 1] = Cell::Taken
                     }
-                    Cell::Taken => {
-                        if self.count_neighbors(x, y, &Cell::Taken) >= 4 {
-                            modified = true;
-                            self.next_buf[y + 1][x + 1] = Cell::Empty
-                        }
+                    Cell::Taken if self.count_neighbors(x, y, &Cell::Taken) >= 4 => {
+                        modified = true;
+                        self.next_buf[y + 1][x + 1] = Cell::Empty
                     }
                     _ => {}
                 }
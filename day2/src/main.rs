@@ -17,8 +17,8 @@ impl FromStr for Entry {
         let space = s.find(' ').unwrap();
         let colon = s.find(':').unwrap();
 
-        let range_start = (&s[..hyphen]).parse::<u16>()?;
-        let range_end = (&s[hyphen + 1..space]).parse::<u16>()?;
+        let range_start = s[..hyphen].parse::<u16>()?;
+        let range_end = s[hyphen + 1..space].parse::<u16>()?;
 
         let letter = s.chars().nth(space + 1).unwrap();
 
@@ -64,7 +64,7 @@ fn parse_map<T: Deref<Target = str>>(input: &[T]) -> Vec<Vec<Field>> {
 
 #[test]
 fn test_hits() {
-    const TEST_INPUT: [&'static str; 11] = [
+    const TEST_INPUT: [&str; 11] = [
         "..##.......",
         "#...#...#..",
         ".#....#..#.",
@@ -1,12 +1,40 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    str::FromStr,
+};
+
 use anyhow::Result;
 
+mod record;
+
+const USAGE: &str = "usage: day4 [error|warn|ignore]
+       day4 export <csv|json> <records file> <rejects file> [error|warn|ignore]";
+
 fn main() -> Result<()> {
     let input: Vec<String> = input::read_paragraphs("inputs/4")?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    let unknown = match args.as_slice() {
+        [] | ["export", _, _, _] => UnknownKeys::Warn,
+        [unknown] | ["export", _, _, _, unknown] => unknown.parse()?,
+        _ => anyhow::bail!(USAGE),
+    };
+
+    if let ["export", format, records, rejects, ..] = args.as_slice() {
+        let mut records = BufWriter::new(File::create(records)?);
+        let mut rejects = BufWriter::new(File::create(rejects)?);
+        record::export(&input, unknown, format.parse()?, &mut records, &mut rejects)?;
+        records.flush()?;
+        rejects.flush()?;
+        return Ok(());
+    }
 
-    let count = input.iter().filter(|e| is_valid_pt1(*e)).count();
+    let count = input.iter().filter(|e| is_valid_pt1(e)).count();
     println!("part 1: {}", count);
 
-    let count = input.iter().filter(|e| is_valid_pt2(*e)).count();
+    let count = input.iter().filter(|e| is_valid_pt2(e, unknown)).count();
     println!("part 2: {}", count);
 
     Ok(())
@@ -22,70 +50,174 @@ fn is_valid_pt1(passport: &str) -> bool {
         && passport.contains("pid")
 }
 
-fn is_valid_pt2(passport: &str) -> bool {
-    // byr, iyr, eyr, hgt, hcl, ecl, pid, cid
-    let mut valid: u8 = 0;
-    let entries = passport
-        .split(|c: char| c.is_whitespace() || c == '\n')
-        .filter(|s| s.trim() != "");
-    for entry in entries {
-        match &entry[0..3] {
-            "byr" => match entry[4..].parse() {
-                Ok(1920..=2002) => valid |= 1 << 7,
-                _ => continue,
-            },
-            "iyr" => match entry[4..].parse() {
-                Ok(2010..=2020) => valid |= 1 << 6,
-                _ => continue,
-            },
-            "eyr" => match entry[4..].parse() {
-                Ok(2020..=2030) => valid |= 1 << 5,
-                _ => continue,
-            },
-            "hgt" => {
-                let rest = &entry[4..];
-                let unit = rest.trim_start_matches(|c: char| c.is_numeric());
-                let num = rest.strip_suffix(unit).unwrap().parse();
-                match (unit, num) {
-                    ("cm", Ok(150..=193)) => valid |= 1 << 4,
-                    ("in", Ok(59..=76)) => valid |= 1 << 4,
-                    _ => continue,
-                }
-            }
-            "hcl" => {
-                if entry.chars().nth(4) != Some('#') {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnknownKeys {
+    Error,
+    Warn,
+    Ignore,
+}
+
+impl FromStr for UnknownKeys {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(UnknownKeys::Error),
+            "warn" => Ok(UnknownKeys::Warn),
+            "ignore" => Ok(UnknownKeys::Ignore),
+            _ => Err(anyhow::anyhow!(
+                "expected error, warn or ignore, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Byr,
+    Iyr,
+    Eyr,
+    Hgt,
+    Hcl,
+    Ecl,
+    Pid,
+    Cid,
+}
+
+impl Key {
+    fn name(self) -> &'static str {
+        match self {
+            Key::Byr => "byr",
+            Key::Iyr => "iyr",
+            Key::Eyr => "eyr",
+            Key::Hgt => "hgt",
+            Key::Hcl => "hcl",
+            Key::Ecl => "ecl",
+            Key::Pid => "pid",
+            Key::Cid => "cid",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << (7 - self as u8)
+    }
+}
+
+impl FromStr for Key {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "byr" => Ok(Key::Byr),
+            "iyr" => Ok(Key::Iyr),
+            "eyr" => Ok(Key::Eyr),
+            "hgt" => Ok(Key::Hgt),
+            "hcl" => Ok(Key::Hcl),
+            "ecl" => Ok(Key::Ecl),
+            "pid" => Ok(Key::Pid),
+            "cid" => Ok(Key::Cid),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field<'a> {
+    key: Key,
+    value: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FieldError {
+    MissingColon(String),
+    DuplicateKey(Key),
+    UnknownKey(String),
+    NonAscii(String),
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::MissingColon(field) => write!(f, "field `{}` has no colon", field),
+            FieldError::DuplicateKey(key) => write!(f, "duplicate key `{}`", key.name()),
+            FieldError::UnknownKey(key) => write!(f, "unknown key `{}`", key),
+            FieldError::NonAscii(field) => write!(f, "field `{}` is not ASCII", field),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+fn tokenize(passport: &str, unknown: UnknownKeys) -> Result<Vec<Field<'_>>, FieldError> {
+    let mut seen: u8 = 0;
+    let mut fields = Vec::new();
+    for entry in passport.split_whitespace() {
+        if !entry.is_ascii() {
+            return Err(FieldError::NonAscii(entry.to_string()));
+        }
+        let (key, value) = match entry.split_once(':') {
+            Some(kv) => kv,
+            None => return Err(FieldError::MissingColon(entry.to_string())),
+        };
+        let key = match key.parse::<Key>() {
+            Ok(key) => key,
+            Err(()) => match unknown {
+                UnknownKeys::Error => return Err(FieldError::UnknownKey(key.to_string())),
+                UnknownKeys::Warn => {
+                    eprintln!("warning: {}", FieldError::UnknownKey(key.to_string()));
                     continue;
                 }
-                // Discard every correct char, check if there are any left
-                if entry[5..]
-                    .chars()
-                    .filter(|c| !match c {
-                        '0'..='9' => true,
-                        'a'..='f' => true,
-                        _ => false,
-                    })
-                    .count()
-                    == 0
-                {
-                    valid |= 1 << 3
-                }
-            }
-            "ecl" => match &entry[4..] {
-                "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth" => valid |= 1 << 2,
-                _ => continue,
+                UnknownKeys::Ignore => continue,
             },
-            "pid" => {
-                if entry[4..].chars().filter(|c| c.is_numeric()).count() == 9 {
-                    valid |= 1 << 1;
-                }
-            }
-            // Doesn't matter, could be removed
-            "cid" => valid |= 1,
-            _ => unreachable!(),
+        };
+        if seen & key.bit() != 0 {
+            return Err(FieldError::DuplicateKey(key));
         }
+        seen |= key.bit();
+        fields.push(Field { key, value });
     }
+    Ok(fields)
+}
+
+fn is_valid_field(field: &Field) -> bool {
+    let value = field.value;
+    match field.key {
+        Key::Byr => matches!(value.parse(), Ok(1920..=2002)),
+        Key::Iyr => matches!(value.parse(), Ok(2010..=2020)),
+        Key::Eyr => matches!(value.parse(), Ok(2020..=2030)),
+        Key::Hgt => {
+            let unit_start = value
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(value.len());
+            let (num, unit) = value.split_at(unit_start);
+            matches!(
+                (unit, num.parse()),
+                ("cm", Ok(150..=193)) | ("in", Ok(59..=76))
+            )
+        }
+        Key::Hcl => match value.strip_prefix('#') {
+            Some(hex) => hex.len() == 6 && hex.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')),
+            None => false,
+        },
+        Key::Ecl => matches!(value, "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth"),
+        Key::Pid => value.len() == 9 && value.chars().all(|c| c.is_ascii_digit()),
+        // Doesn't matter, could be removed
+        Key::Cid => true,
+    }
+}
+
+fn is_valid_pt2(passport: &str, unknown: UnknownKeys) -> bool {
+    let fields = match tokenize(passport, unknown) {
+        Ok(fields) => fields,
+        Err(_) => return false,
+    };
+    let valid = fields
+        .iter()
+        .filter(|f| is_valid_field(f))
+        .fold(0, |valid, f| valid | f.key.bit());
     // Last bit is optional
-    valid >= u8::MAX - 1
+    valid | Key::Cid.bit() == u8::MAX
 }
 
 #[test]
@@ -106,7 +238,7 @@ fn test_part_1() {
         iyr:2011 ecl:brn hgt:59in
     ";
     let input: Vec<&str> = input.split("\n\n").collect();
-    assert_eq!(input.iter().filter(|e| is_valid_pt1(*e)).count(), 2);
+    assert_eq!(input.iter().filter(|e| is_valid_pt1(e)).count(), 2);
 }
 
 #[test]
@@ -127,7 +259,7 @@ fn test_valid() {
     ";
     let input: Vec<&str> = input.split("\n\n").collect();
     for pass in input {
-        assert!(is_valid_pt2(pass))
+        assert!(is_valid_pt2(pass, UnknownKeys::Error))
     }
 }
 
@@ -150,6 +282,86 @@ fn test_invalid() {
     ";
     let input: Vec<&str> = input.split("\n\n").collect();
     for pass in input {
-        assert!(!is_valid_pt2(pass))
+        assert!(!is_valid_pt2(pass, UnknownKeys::Error))
+    }
+}
+
+#[test]
+fn test_tokenize_errors() {
+    assert_eq!(
+        tokenize("byr:1937 iyr", UnknownKeys::Error),
+        Err(FieldError::MissingColon("iyr".to_string()))
+    );
+    assert_eq!(
+        tokenize("byr:1937 byr:1940", UnknownKeys::Error),
+        Err(FieldError::DuplicateKey(Key::Byr))
+    );
+    assert_eq!(
+        tokenize("byr:1937 foo:bar", UnknownKeys::Error),
+        Err(FieldError::UnknownKey("foo".to_string()))
+    );
+    assert_eq!(
+        tokenize("byr:1937 hcl:#ffffé", UnknownKeys::Error),
+        Err(FieldError::NonAscii("hcl:#ffffé".to_string()))
+    );
+}
+
+#[test]
+fn test_tokenize_unknown_keys() {
+    let expected = vec![Field {
+        key: Key::Byr,
+        value: "1937",
+    }];
+    assert_eq!(
+        tokenize("byr:1937 foo:bar", UnknownKeys::Warn),
+        Ok(expected.clone())
+    );
+    assert_eq!(
+        tokenize("byr:1937 foo:bar", UnknownKeys::Ignore),
+        Ok(expected)
+    );
+}
+
+#[test]
+fn test_fuzz_no_panic() {
+    const PIECES: [&str; 20] = [
+        "byr",
+        "iyr",
+        "eyr",
+        "hgt",
+        "hcl",
+        "ecl",
+        "pid",
+        "cid",
+        "xyz",
+        ":",
+        "#",
+        "cm",
+        "in",
+        "1920",
+        "0",
+        "ff",
+        "é",
+        "\u{1F600}",
+        " ",
+        "\n",
+    ];
+    // xorshift, so the test is reproducible without extra dependencies
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..10_000 {
+        let len = next() % 16;
+        let passport: String = (0..len)
+            .map(|_| PIECES[(next() % PIECES.len() as u64) as usize])
+            .collect();
+        for &unknown in &[UnknownKeys::Error, UnknownKeys::Warn, UnknownKeys::Ignore] {
+            let _ = tokenize(&passport, unknown);
+            is_valid_pt2(&passport, unknown);
+        }
     }
 }
@@ -0,0 +1,329 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use super::{is_valid_field, tokenize, Field, FieldError, Key, UnknownKeys};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow::anyhow!("expected csv or json, got `{}`", s)),
+        }
+    }
+}
+
+/// A passport with every field checked as in part 2 and converted to a single
+/// canonical form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passport {
+    pub byr: u16,
+    pub iyr: u16,
+    pub eyr: u16,
+    pub height_cm: u16,
+    /// `#rrggbb`
+    pub hair: String,
+    pub eye: String,
+    pub pid: String,
+    pub cid: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Invalid {
+    Field(FieldError),
+    Missing(Key),
+    Value(Key, String),
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invalid::Field(err) => err.fmt(f),
+            Invalid::Missing(key) => write!(f, "missing {}", key.name()),
+            Invalid::Value(key, value) => write!(f, "invalid {} `{}`", key.name(), value),
+        }
+    }
+}
+
+fn height_cm(value: &str) -> Option<u16> {
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(unit_start);
+    let num: u16 = num.parse().ok()?;
+    match unit {
+        "cm" => Some(num),
+        // Round to the nearest centimetre
+        "in" => Some((num * 254 + 50) / 100),
+        _ => None,
+    }
+}
+
+/// Converts a field that passes the same checks as part 2
+fn normalize_field<T>(
+    fields: &[Field],
+    key: Key,
+    reasons: &mut Vec<Invalid>,
+    normalize: impl Fn(&str) -> Option<T>,
+) -> Option<T> {
+    match fields.iter().find(|f| f.key == key) {
+        Some(field) => {
+            let value = Some(field)
+                .filter(|field| is_valid_field(field))
+                .and_then(|field| normalize(field.value));
+            if value.is_none() {
+                reasons.push(Invalid::Value(key, field.value.to_string()));
+            }
+            value
+        }
+        None => {
+            if key != Key::Cid {
+                reasons.push(Invalid::Missing(key));
+            }
+            None
+        }
+    }
+}
+
+impl Passport {
+    /// Normalizes a single paragraph, collecting every reason it was rejected.
+    pub fn parse(passport: &str, unknown: UnknownKeys) -> Result<Passport, Vec<Invalid>> {
+        let fields = tokenize(passport, unknown).map_err(|e| vec![Invalid::Field(e)])?;
+        let mut reasons = Vec::new();
+        let text = |v: &str| Some(v.to_string());
+        let byr = normalize_field(&fields, Key::Byr, &mut reasons, |v| v.parse().ok());
+        let iyr = normalize_field(&fields, Key::Iyr, &mut reasons, |v| v.parse().ok());
+        let eyr = normalize_field(&fields, Key::Eyr, &mut reasons, |v| v.parse().ok());
+        let hgt = normalize_field(&fields, Key::Hgt, &mut reasons, height_cm);
+        let hcl = normalize_field(&fields, Key::Hcl, &mut reasons, text);
+        let ecl = normalize_field(&fields, Key::Ecl, &mut reasons, text);
+        let pid = normalize_field(&fields, Key::Pid, &mut reasons, text);
+        let cid = normalize_field(&fields, Key::Cid, &mut reasons, text);
+
+        match (byr, iyr, eyr, hgt, hcl, ecl, pid) {
+            (
+                Some(byr),
+                Some(iyr),
+                Some(eyr),
+                Some(height_cm),
+                Some(hair),
+                Some(eye),
+                Some(pid),
+            ) => Ok(Passport {
+                byr,
+                iyr,
+                eyr,
+                height_cm,
+                hair,
+                eye,
+                pid,
+                cid,
+            }),
+            _ => Err(reasons),
+        }
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes the valid passports to `records` and the rest, with the reasons they were
+/// rejected, to `rejects`. Rejects are identified by their 1-based paragraph number.
+pub fn export<T, W, R>(
+    passports: &[T],
+    unknown: UnknownKeys,
+    format: Format,
+    records: &mut W,
+    rejects: &mut R,
+) -> io::Result<()>
+where
+    T: AsRef<str>,
+    W: Write,
+    R: Write,
+{
+    if format == Format::Csv {
+        writeln!(records, "byr,iyr,eyr,hgt_cm,hcl,ecl,pid,cid")?;
+        writeln!(rejects, "paragraph,reasons")?;
+    }
+    for (i, passport) in passports.iter().enumerate() {
+        let paragraph = i + 1;
+        match (Passport::parse(passport.as_ref(), unknown), format) {
+            (Ok(p), Format::Csv) => writeln!(
+                records,
+                "{},{},{},{},{},{},{},{}",
+                p.byr,
+                p.iyr,
+                p.eyr,
+                p.height_cm,
+                p.hair,
+                p.eye,
+                p.pid,
+                csv_field(p.cid.as_deref().unwrap_or(""))
+            )?,
+            (Ok(p), Format::Json) => writeln!(
+                records,
+                "{{\"byr\":{},\"iyr\":{},\"eyr\":{},\"hgt_cm\":{},\"hcl\":{},\"ecl\":{},\"pid\":{},\"cid\":{}}}",
+                p.byr,
+                p.iyr,
+                p.eyr,
+                p.height_cm,
+                json_string(&p.hair),
+                json_string(&p.eye),
+                json_string(&p.pid),
+                p.cid.as_deref().map_or("null".to_string(), json_string)
+            )?,
+            (Err(reasons), Format::Csv) => {
+                let reasons: Vec<String> = reasons.iter().map(|r| r.to_string()).collect();
+                writeln!(rejects, "{},{}", paragraph, csv_field(&reasons.join("; ")))?
+            }
+            (Err(reasons), Format::Json) => {
+                let reasons: Vec<String> =
+                    reasons.iter().map(|r| json_string(&r.to_string())).collect();
+                writeln!(
+                    rejects,
+                    "{{\"paragraph\":{},\"reasons\":[{}]}}",
+                    paragraph,
+                    reasons.join(",")
+                )?
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_normalize() {
+    let passport = Passport::parse(
+        "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f",
+        UnknownKeys::Error,
+    );
+    assert_eq!(
+        passport,
+        Ok(Passport {
+            byr: 1980,
+            iyr: 2012,
+            eyr: 2030,
+            height_cm: 188,
+            hair: "#623a2f".to_string(),
+            eye: "grn".to_string(),
+            pid: "087499704".to_string(),
+            cid: None,
+        })
+    );
+
+    // Exported records are exactly the passports part 2 counts
+    let passport = "pid:087499704 hgt:74in ecl:GRN iyr:2012 eyr:2030 byr:1980 hcl:#62A";
+    assert!(!super::is_valid_pt2(passport, UnknownKeys::Error));
+    assert_eq!(
+        Passport::parse(passport, UnknownKeys::Error),
+        Err(vec![
+            Invalid::Value(Key::Hcl, "#62A".to_string()),
+            Invalid::Value(Key::Ecl, "GRN".to_string()),
+        ])
+    );
+    let passport = passport
+        .replace("ecl:GRN", "ecl:grn")
+        .replace("#62A", "#62a");
+    assert!(!super::is_valid_pt2(&passport, UnknownKeys::Error));
+    assert_eq!(
+        Passport::parse(&passport, UnknownKeys::Error),
+        Err(vec![Invalid::Value(Key::Hcl, "#62a".into())])
+    );
+}
+
+#[test]
+fn test_reject_reasons() {
+    let passport = Passport::parse(
+        "eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170",
+        UnknownKeys::Error,
+    );
+    assert_eq!(
+        passport,
+        Err(vec![
+            Invalid::Missing(Key::Byr),
+            Invalid::Missing(Key::Iyr),
+            Invalid::Value(Key::Eyr, "1972".to_string()),
+            Invalid::Value(Key::Hgt, "170".to_string()),
+            Invalid::Missing(Key::Pid),
+        ])
+    );
+}
+
+#[test]
+fn test_export() {
+    let passports = [
+        "hcl:#888785 hgt:164cm byr:2001 iyr:2015 cid:88 pid:545766238 ecl:hzl eyr:2022",
+        "hcl:dab227 iyr:2012 ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992",
+    ];
+
+    let (mut records, mut rejects) = (Vec::new(), Vec::new());
+    export(
+        &passports,
+        UnknownKeys::Error,
+        Format::Csv,
+        &mut records,
+        &mut rejects,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(records).unwrap(),
+        "byr,iyr,eyr,hgt_cm,hcl,ecl,pid,cid\n2001,2015,2022,164,#888785,hzl,545766238,88\n"
+    );
+    assert_eq!(
+        String::from_utf8(rejects).unwrap(),
+        "paragraph,reasons\n2,invalid hcl `dab227`\n"
+    );
+
+    let (mut records, mut rejects) = (Vec::new(), Vec::new());
+    export(
+        &passports,
+        UnknownKeys::Error,
+        Format::Json,
+        &mut records,
+        &mut rejects,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(records).unwrap(),
+        "{\"byr\":2001,\"iyr\":2015,\"eyr\":2022,\"hgt_cm\":164,\"hcl\":\"#888785\",\
+         \"ecl\":\"hzl\",\"pid\":\"545766238\",\"cid\":\"88\"}\n"
+    );
+    assert_eq!(
+        String::from_utf8(rejects).unwrap(),
+        "{\"paragraph\":2,\"reasons\":[\"invalid hcl `dab227`\"]}\n"
+    );
+}
@@ -1,58 +1,395 @@
+use std::{fmt, str::FromStr};
+
 use anyhow::Result;
 
+mod seat_map;
+
+use seat_map::SeatMap;
+
 fn main() -> Result<()> {
+    let mut layout = Layout::default();
+    let mut show_map = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "map" => show_map = true,
+            _ => layout = arg.parse()?,
+        }
+    }
     let input: Vec<String> = input::read_lines("inputs/5")?;
-    let input: Vec<(&str, &str)> = input.iter().map(|s| s.split_at(7)).collect();
+    let input: Vec<BoardingPass> = input
+        .iter()
+        .map(|s| layout.parse_pass(s))
+        .collect::<Result<_, _>>()?;
 
-    let mut taken: [u8; 128] = [0; 128];
+    let mut map = SeatMap::new(layout);
     let mut max_id = 0;
     for seat in input {
-        let row = find_num(seat.0, 'F', 127);
-        let col = find_num(seat.1, 'L', 7);
-        let id = (row as u32 * 8) + col as u32;
+        let id = seat.seat_id();
         // part 1
         if id > max_id {
             max_id = id
         }
         // part 2
-        taken[row as usize] |= 1 << col;
+        map.insert(&seat);
     }
 
     println!("part 1: {}", max_id);
 
-    // find a 0 (empty seat)
-    for (row, s) in taken.iter().enumerate() {
-        if *s < u8::MAX && s.count_zeros() == 1 {
-            for col in 0..8 {
-                if (*s & (1 << col)) == 0 {
-                    let id = (row * 8) + col;
-                    println!("part 2: {}", id)
-                }
-            }
+    // find an empty seat between two taken ones
+    for pass in map.gaps() {
+        println!("part 2: {} ({})", pass.seat_id(), pass)
+    }
+
+    if show_map {
+        print!("{}", map);
+        println!("free seats: {}", map.free_seats().len());
+        if let Some((row, cols)) = map.largest_free_block() {
+            println!(
+                "largest free block: row {}, columns {}..{} ({} seats)",
+                row,
+                cols.start,
+                cols.end,
+                cols.len()
+            );
         }
     }
 
     Ok(())
 }
 
-fn find_num(desc: &str, lower: char, max: u8) -> u8 {
-    let mut row = max;
-    let mut div = max;
+/// The number of rows and columns in the cabin. Sizes don't have to be powers of two,
+/// partitioning always keeps the larger half at the front.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    rows: u32,
+    cols: u32,
+}
+
+/// The most seats a layout can have, so a seat map always fits in memory
+const MAX_SEATS: u32 = 1 << 24;
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { rows: 128, cols: 8 }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    /// Parses `<rows>x<cols>`, e.g. `128x8`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rows, cols) = s
+            .split_once('x')
+            .ok_or_else(|| anyhow::anyhow!("expected <rows>x<cols>, got `{}`", s))?;
+        Layout::new(rows.parse()?, cols.parse()?)
+            .ok_or_else(|| anyhow::anyhow!("layout `{}` needs 1 to {} seats", s, MAX_SEATS))
+    }
+}
+
+impl Layout {
+    pub fn new(rows: u32, cols: u32) -> Option<Layout> {
+        let fits = |size: u32| size > 0 && size.checked_next_power_of_two().is_some();
+        match rows.checked_mul(cols) {
+            Some(seats) if fits(rows) && fits(cols) && seats <= MAX_SEATS => {
+                Some(Layout { rows, cols })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn seats(&self) -> u32 {
+        self.rows * self.cols
+    }
+
+    pub fn row_letters(&self) -> usize {
+        letters(self.rows)
+    }
+
+    pub fn col_letters(&self) -> usize {
+        letters(self.cols)
+    }
+
+    pub fn parse_pass(&self, s: &str) -> Result<BoardingPass, PassError> {
+        let (row_letters, col_letters) = (self.row_letters(), self.col_letters());
+        let len = s.chars().count();
+        if len != row_letters + col_letters {
+            return Err(PassError::Length {
+                expected: row_letters + col_letters,
+                found: len,
+            });
+        }
+        for (index, found) in s.chars().enumerate() {
+            let expected = if index < row_letters {
+                ('F', 'B')
+            } else {
+                ('L', 'R')
+            };
+            if found != expected.0 && found != expected.1 {
+                return Err(PassError::InvalidChar {
+                    index,
+                    found,
+                    expected,
+                });
+            }
+        }
+        // Only ASCII is left, so the split is on a char boundary
+        let (row, col) = s.split_at(row_letters);
+        let row = partition(row, 'F', self.rows).ok_or(PassError::OutsideLayout)?;
+        let col = partition(col, 'L', self.cols).ok_or(PassError::OutsideLayout)?;
+        Ok(BoardingPass {
+            row,
+            col,
+            layout: *self,
+        })
+    }
+}
+
+/// The number of halvings needed to narrow `size` down to a single seat
+fn letters(size: u32) -> usize {
+    (size.next_power_of_two().trailing_zeros()) as usize
+}
+
+/// Splits `lo..hi` in two, the front half getting the extra seat on odd sizes
+fn midpoint(lo: u32, hi: u32) -> u32 {
+    lo + (hi - lo).div_ceil(2)
+}
+
+/// Follows `desc` down the halves of `0..size`, returning `None` if it ends up in an
+/// empty half.
+fn partition(desc: &str, lower: char, size: u32) -> Option<u32> {
+    let (mut lo, mut hi) = (0, size);
     for c in desc.chars() {
-        div /= 2;
+        let mid = midpoint(lo, hi);
         if c == lower {
-            row -= div + 1;
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+        if lo >= hi {
+            return None;
         }
     }
-    row
+    Some(lo)
+}
+
+/// The inverse of `partition`
+fn encode(
+    value: u32,
+    size: u32,
+    (lower, upper): (char, char),
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    let (mut lo, mut hi) = (0, size);
+    for _ in 0..letters(size) {
+        let mid = midpoint(lo, hi);
+        if value < mid {
+            hi = mid;
+            write!(f, "{}", lower)?;
+        } else {
+            lo = mid;
+            write!(f, "{}", upper)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PassError {
+    Length {
+        expected: usize,
+        found: usize,
+    },
+    InvalidChar {
+        index: usize,
+        found: char,
+        expected: (char, char),
+    },
+    OutsideLayout,
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PassError::Length { expected, found } => {
+                write!(f, "expected {} characters, got {}", expected, found)
+            }
+            PassError::InvalidChar {
+                index,
+                found,
+                expected: (lower, upper),
+            } => write!(
+                f,
+                "expected {} or {} at {}, got `{}`",
+                lower, upper, index, found
+            ),
+            PassError::OutsideLayout => write!(f, "seat is outside the cabin"),
+        }
+    }
+}
+
+impl std::error::Error for PassError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BoardingPass {
+    row: u32,
+    col: u32,
+    layout: Layout,
+}
+
+impl BoardingPass {
+    pub fn row(&self) -> u32 {
+        self.row
+    }
+
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    pub fn seat_id(&self) -> u32 {
+        (self.row * self.layout.cols) + self.col
+    }
+
+    /// Returns `None` if the id is outside the cabin.
+    pub fn from_seat_id(id: u32, layout: Layout) -> Option<BoardingPass> {
+        if id >= layout.seats() {
+            return None;
+        }
+        Some(BoardingPass {
+            row: id / layout.cols,
+            col: id % layout.cols,
+            layout,
+        })
+    }
+}
+
+impl FromStr for BoardingPass {
+    type Err = PassError;
+
+    /// Parses a pass for the default 128 x 8 cabin
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Layout::default().parse_pass(s)
+    }
+}
+
+impl fmt::Display for BoardingPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        encode(self.row, self.layout.rows, ('F', 'B'), f)?;
+        encode(self.col, self.layout.cols, ('L', 'R'), f)
+    }
 }
 
 #[test]
-fn test_find_num() {
+fn test_partition() {
     let input = "FBFBBFF";
-    let row = find_num(&input, 'F', 127);
-    assert_eq!(row, 44);
+    let row = partition(input, 'F', 128);
+    assert_eq!(row, Some(44));
     let input = "RLR";
-    let column = find_num(&input, 'L', 7);
-    assert_eq!(column, 5);
+    let column = partition(input, 'L', 8);
+    assert_eq!(column, Some(5));
+}
+
+#[test]
+fn test_boarding_pass() {
+    let pass: BoardingPass = "FBFBBFFRLR".parse().unwrap();
+    assert_eq!(pass.row(), 44);
+    assert_eq!(pass.col(), 5);
+    assert_eq!(pass.seat_id(), 357);
+
+    let pass: BoardingPass = "BBFFBBFRLL".parse().unwrap();
+    assert_eq!((pass.row(), pass.col(), pass.seat_id()), (102, 4, 820));
+}
+
+#[test]
+fn test_boarding_pass_errors() {
+    assert_eq!(
+        "FBFBBFFRL".parse::<BoardingPass>(),
+        Err(PassError::Length {
+            expected: 10,
+            found: 9
+        })
+    );
+    assert_eq!(
+        "FBFBBFFRLRR".parse::<BoardingPass>(),
+        Err(PassError::Length {
+            expected: 10,
+            found: 11
+        })
+    );
+    assert_eq!(
+        "FBFBBFLRLR".parse::<BoardingPass>(),
+        Err(PassError::InvalidChar {
+            index: 6,
+            found: 'L',
+            expected: ('F', 'B')
+        })
+    );
+    assert_eq!(
+        "FBFBBFFRLB".parse::<BoardingPass>(),
+        Err(PassError::InvalidChar {
+            index: 9,
+            found: 'B',
+            expected: ('L', 'R')
+        })
+    );
+    assert_eq!(
+        "FBFBBFFRLé".parse::<BoardingPass>(),
+        Err(PassError::InvalidChar {
+            index: 9,
+            found: 'é',
+            expected: ('L', 'R')
+        })
+    );
+    assert_eq!(BoardingPass::from_seat_id(1024, Layout::default()), None);
+}
+
+#[test]
+fn test_round_trip() {
+    for id in 0..1024 {
+        let pass = BoardingPass::from_seat_id(id, Layout::default()).unwrap();
+        assert_eq!(pass.seat_id(), id);
+        let encoded = pass.to_string();
+        let decoded: BoardingPass = encoded.parse().unwrap();
+        assert_eq!(decoded, pass);
+        assert_eq!(decoded.to_string(), encoded);
+    }
+}
+
+#[test]
+fn test_layouts() {
+    let layout: Layout = "32x4".parse().unwrap();
+    assert_eq!((layout.row_letters(), layout.col_letters()), (5, 2));
+    let pass = layout.parse_pass("BFFFBRL").unwrap();
+    assert_eq!((pass.row(), pass.col(), pass.seat_id()), (17, 2, 70));
+
+    // 5 rows: 0..3 | 3..5, then 0..2 | 2..3 and 3..4 | 4..5, ...
+    let layout = Layout::new(5, 3).unwrap();
+    assert_eq!((layout.row_letters(), layout.col_letters()), (3, 2));
+    assert_eq!(layout.parse_pass("BFFRL").unwrap().row(), 3);
+    assert_eq!(layout.parse_pass("BBFLR").unwrap().seat_id(), 4 * 3 + 1);
+    assert_eq!(layout.parse_pass("BBBLL"), Err(PassError::OutsideLayout));
+    assert_eq!(layout.parse_pass("FFFRR"), Err(PassError::OutsideLayout));
+
+    assert_eq!(Layout::new(0, 8), None);
+    assert_eq!(Layout::new(u32::MAX / 2 + 1, 1), None);
+    assert!(Layout::new(1 << 12, 1 << 12).is_some());
+    assert_eq!(Layout::new(1 << 12, 1 << 13), None);
+    assert!("3000000000x1".parse::<Layout>().is_err());
+    assert!("128by8".parse::<Layout>().is_err());
+}
+
+#[test]
+fn test_layout_round_trip() {
+    for rows in 1..20 {
+        for cols in 1..10 {
+            let layout = Layout::new(rows, cols).unwrap();
+            for id in 0..layout.seats() {
+                let pass = BoardingPass::from_seat_id(id, layout).unwrap();
+                let decoded = layout.parse_pass(&pass.to_string()).unwrap();
+                assert_eq!(decoded, pass);
+                assert_eq!(decoded.seat_id(), id);
+            }
+            assert_eq!(BoardingPass::from_seat_id(layout.seats(), layout), None);
+        }
+    }
 }
@@ -0,0 +1,143 @@
+use std::{fmt, ops::Range};
+
+use super::{BoardingPass, Layout};
+
+/// Which seats of a cabin are taken, indexed by seat id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeatMap {
+    layout: Layout,
+    taken: Vec<bool>,
+}
+
+impl SeatMap {
+    pub fn new(layout: Layout) -> SeatMap {
+        SeatMap {
+            layout,
+            taken: vec![false; layout.seats() as usize],
+        }
+    }
+
+    /// Marks the seat as taken. Passes for a different layout are placed by row and
+    /// column, returning `false` if they don't fit.
+    pub fn insert(&mut self, pass: &BoardingPass) -> bool {
+        if pass.row() >= self.layout.rows || pass.col() >= self.layout.cols {
+            return false;
+        }
+        let index = self.index(pass.row(), pass.col());
+        self.taken[index] = true;
+        true
+    }
+
+    pub fn is_taken(&self, row: u32, col: u32) -> bool {
+        row < self.layout.rows && col < self.layout.cols && self.taken[self.index(row, col)]
+    }
+
+    fn index(&self, row: u32, col: u32) -> usize {
+        (row * self.layout.cols + col) as usize
+    }
+
+    fn pass(&self, id: usize) -> BoardingPass {
+        // Every index into `taken` is a seat in the layout
+        BoardingPass::from_seat_id(id as u32, self.layout).unwrap()
+    }
+
+    pub fn free_seats(&self) -> Vec<BoardingPass> {
+        (0..self.taken.len())
+            .filter(|id| !self.taken[*id])
+            .map(|id| self.pass(id))
+            .collect()
+    }
+
+    /// Free seats whose neighbouring seat ids are both taken
+    pub fn gaps(&self) -> Vec<BoardingPass> {
+        (1..self.taken.len().saturating_sub(1))
+            .filter(|id| !self.taken[*id] && self.taken[id - 1] && self.taken[id + 1])
+            .map(|id| self.pass(id))
+            .collect()
+    }
+
+    /// The longest run of free seats within a single row, as the row and its columns.
+    /// Ties go to the front of the cabin.
+    pub fn largest_free_block(&self) -> Option<(u32, Range<u32>)> {
+        let mut best: Option<(u32, Range<u32>)> = None;
+        for row in 0..self.layout.rows {
+            let mut start = 0;
+            for col in 0..=self.layout.cols {
+                if col < self.layout.cols && !self.is_taken(row, col) {
+                    continue;
+                }
+                let len = col - start;
+                if len > 0 && best.as_ref().is_none_or(|(_, b)| len > b.len() as u32) {
+                    best = Some((row, start..col));
+                }
+                start = col + 1;
+            }
+        }
+        best
+    }
+
+    /// The number of taken seats in each row
+    pub fn row_occupancy(&self) -> Vec<u32> {
+        self.taken
+            .chunks(self.layout.cols as usize)
+            .map(|row| row.iter().filter(|t| **t).count() as u32)
+            .collect()
+    }
+}
+
+impl fmt::Display for SeatMap {
+    /// One line per row, `#` for taken and `.` for free seats
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = (self.layout.rows - 1).to_string().len();
+        let occupancy = self.row_occupancy();
+        for (row, seats) in self.taken.chunks(self.layout.cols as usize).enumerate() {
+            write!(f, "{:>width$} ", row, width = width)?;
+            for taken in seats {
+                write!(f, "{}", if *taken { '#' } else { '.' })?;
+            }
+            writeln!(f, " {}/{}", occupancy[row], self.layout.cols)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_map() -> SeatMap {
+    // 4 rows of 4, row 1 has a gap at col 1, row 3 is empty
+    let layout = Layout::new(4, 4).unwrap();
+    let mut map = SeatMap::new(layout);
+    for id in (0..12).filter(|id| *id != 5 && *id != 10) {
+        map.insert(&BoardingPass::from_seat_id(id, layout).unwrap());
+    }
+    map
+}
+
+#[test]
+fn test_free_seats_and_gaps() {
+    let map = test_map();
+    let free: Vec<u32> = map.free_seats().iter().map(|p| p.seat_id()).collect();
+    assert_eq!(free, vec![5, 10, 12, 13, 14, 15]);
+    let gaps: Vec<u32> = map.gaps().iter().map(|p| p.seat_id()).collect();
+    assert_eq!(gaps, vec![5, 10]);
+}
+
+#[test]
+fn test_largest_free_block() {
+    let map = test_map();
+    assert_eq!(map.largest_free_block(), Some((3, 0..4)));
+    assert_eq!(map.row_occupancy(), vec![4, 3, 3, 0]);
+
+    let layout = Layout::new(1, 1).unwrap();
+    let mut full = SeatMap::new(layout);
+    assert!(full.insert(&BoardingPass::from_seat_id(0, layout).unwrap()));
+    assert_eq!(full.largest_free_block(), None);
+    assert!(!full.insert(&BoardingPass::from_seat_id(5, Layout::default()).unwrap()));
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(
+        test_map().to_string(),
+        "0 #### 4/4\n1 #.## 3/4\n2 ##.# 3/4\n3 .... 0/4\n"
+    );
+}
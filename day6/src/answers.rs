@@ -0,0 +1,120 @@
+use std::{
+    fmt,
+    ops::{BitAnd, BitOr, BitXor, Sub},
+};
+
+/// A set of questions `a` to `z`, one bit per question.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Answers(u32);
+
+impl Answers {
+    fn bit(question: char) -> Option<u32> {
+        if question.is_ascii_lowercase() {
+            Some(1 << (question as u8 - b'a'))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `false` if `question` isn't one of `a` to `z`.
+    pub fn insert(&mut self, question: char) -> bool {
+        match Answers::bit(question) {
+            Some(bit) => {
+                self.0 |= bit;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, question: char) -> bool {
+        Answers::bit(question).is_some_and(|bit| self.0 & bit != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn union(self, other: Answers) -> Answers {
+        Answers(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Answers) -> Answers {
+        Answers(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: Answers) -> Answers {
+        Answers(self.0 & !other.0)
+    }
+
+    pub fn symmetric_difference(self, other: Answers) -> Answers {
+        Answers(self.0 ^ other.0)
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = char> {
+        ('a'..='z').filter(move |c| self.contains(*c))
+    }
+}
+
+impl BitOr for Answers {
+    type Output = Answers;
+
+    fn bitor(self, rhs: Answers) -> Answers {
+        self.union(rhs)
+    }
+}
+
+impl BitAnd for Answers {
+    type Output = Answers;
+
+    fn bitand(self, rhs: Answers) -> Answers {
+        self.intersection(rhs)
+    }
+}
+
+impl Sub for Answers {
+    type Output = Answers;
+
+    fn sub(self, rhs: Answers) -> Answers {
+        self.difference(rhs)
+    }
+}
+
+impl BitXor for Answers {
+    type Output = Answers;
+
+    fn bitxor(self, rhs: Answers) -> Answers {
+        self.symmetric_difference(rhs)
+    }
+}
+
+impl fmt::Display for Answers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.iter().try_for_each(|c| write!(f, "{}", c))
+    }
+}
+
+#[cfg(test)]
+fn answers(questions: &str) -> Answers {
+    let mut answers = Answers::default();
+    for c in questions.chars() {
+        assert!(answers.insert(c));
+    }
+    answers
+}
+
+#[test]
+fn test_set_operations() {
+    let (a, b) = (answers("abcx"), answers("bcyz"));
+    assert_eq!(a | b, answers("abcxyz"));
+    assert_eq!(a & b, answers("bc"));
+    assert_eq!(a - b, answers("ax"));
+    assert_eq!(a ^ b, answers("axyz"));
+    assert_eq!((a ^ b).to_string(), "axyz");
+    assert_eq!(answers("abcdefghijklmnopqrstuvwxyz").len(), 26);
+
+    let mut c = Answers::default();
+    assert!(!c.insert('A'));
+    assert!(!c.insert('{'));
+    assert_eq!(c.len(), 0);
+}
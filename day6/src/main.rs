@@ -1,42 +1,237 @@
-use std::ops::Deref;
+use std::{fmt, str::FromStr};
 
 use anyhow::Result;
 
+mod answers;
+mod query;
+mod stats;
+
+use answers::Answers;
+use query::Query;
+
 fn main() -> Result<()> {
-    let input: Vec<String> = input::read_paragraphs("inputs/6")?;
-    println!("part 1: {}", count_answered_any(&input));
-    println!("part 2: {}", count_answered_every(&input));
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let strict = args.iter().any(|a| a == "--strict");
+    args.retain(|a| a != "--strict");
+
+    let input = String::from_utf8(input::read_bytes("inputs/6")?)?;
+    let (groups, diagnostics) = parse_input(&input);
+    for diagnostic in &diagnostics {
+        eprintln!(
+            "{}: {}",
+            if strict { "error" } else { "warning" },
+            diagnostic
+        );
+    }
+    if strict && !diagnostics.is_empty() {
+        anyhow::bail!("{} problems in the input", diagnostics.len());
+    }
+
+    match args.as_slice() {
+        [] => {
+            println!("part 1: {}", count_answered_any(&groups));
+            println!("part 2: {}", count_answered_every(&groups));
+        }
+        [cmd, query] if cmd == "query" => run_query(&groups, &query.parse()?)?,
+        [cmd, format @ ..] if cmd == "stats" && format.len() <= 1 => {
+            let format = match format.first() {
+                Some(format) => format.parse()?,
+                None => stats::Format::Table,
+            };
+            stats::Stats::new(&groups).write(format, &mut std::io::stdout())?
+        }
+        _ => anyhow::bail!("usage: day6 [--strict] [query <query> | stats [table|csv|json]]"),
+    }
     Ok(())
 }
 
-fn count_answered_any<T: Deref<Target = str>>(groups: &[T]) -> usize {
-    groups
-        .iter()
-        .map(|s| ('a'..='z').filter(|c| s.contains(*c)).count())
-        .sum()
-}
-
-fn count_answered_every<T: Deref<Target = str> + std::fmt::Debug>(groups: &[T]) -> usize {
-    let mut sum = 0;
-    for group in groups.iter() {
-        let mut count = 0;
-        let first_line = group.lines().next().unwrap();
-
-        for check_c in first_line.chars() {
-            let mut every = true;
-            for line in group.lines().skip(1) {
-                if !line.contains(check_c) {
-                    every = false;
-                    break;
-                }
+fn run_query(groups: &[Group], query: &Query) -> Result<()> {
+    query.check(groups)?;
+    let mut total = 0;
+    for i in 0..groups.len() {
+        let answers = query.eval(groups, i);
+        println!("group {}: {} {}", i + 1, answers.len(), answers);
+        total += answers.len();
+    }
+    println!("total: {}", total);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Problem {
+    /// Anything but `a` to `z`, the character is skipped
+    InvalidChar(char),
+    /// The same question twice on one line, it's counted once
+    DuplicateAnswer(char),
+    /// More than one blank line between groups
+    EmptyGroup,
+    /// A blank line where only a single group was expected
+    SplitGroup,
+}
+
+/// A problem in the input, lines and columns count from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Diagnostic {
+    line: usize,
+    col: usize,
+    problem: Problem,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.col)?;
+        match self.problem {
+            Problem::InvalidChar(c) => write!(f, "invalid answer {:?}", c),
+            Problem::DuplicateAnswer(c) => write!(f, "`{}` answered twice", c),
+            Problem::EmptyGroup => write!(f, "empty group"),
+            Problem::SplitGroup => write!(f, "blank line inside a group"),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Parses one person's answers, reporting problems at line `line`.
+fn parse_person(line: usize, s: &str, diagnostics: &mut Vec<Diagnostic>) -> Answers {
+    let mut answers = Answers::default();
+    for (i, c) in s.chars().enumerate() {
+        let problem = if answers.contains(c) {
+            Problem::DuplicateAnswer(c)
+        } else if !answers.insert(c) {
+            Problem::InvalidChar(c)
+        } else {
+            continue;
+        };
+        diagnostics.push(Diagnostic {
+            line,
+            col: i + 1,
+            problem,
+        });
+    }
+    answers
+}
+
+/// Splits the input into groups at blank lines. Lines are trimmed, and every problem
+/// is reported but doesn't stop the parse.
+fn parse_input(input: &str) -> (Vec<Group>, Vec<Diagnostic>) {
+    let mut groups = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut people = Vec::new();
+    // The first of two blank lines in a row, once a group has been read
+    let mut empty_group = None;
+    let mut previous_blank = false;
+    for (i, line) in input.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            if !people.is_empty() {
+                groups.push(Group {
+                    people: std::mem::take(&mut people),
+                });
+            } else if previous_blank && !groups.is_empty() && empty_group.is_none() {
+                empty_group = Some(i + 1);
+            }
+            previous_blank = true;
+            continue;
+        }
+        if let Some(line) = empty_group.take() {
+            diagnostics.push(Diagnostic {
+                line,
+                col: 1,
+                problem: Problem::EmptyGroup,
+            });
+        }
+        previous_blank = false;
+        let offset = line.len() - line.trim_start().len();
+        let start = diagnostics.len();
+        people.push(parse_person(i + 1, trimmed, &mut diagnostics));
+        for diagnostic in &mut diagnostics[start..] {
+            diagnostic.col += line[..offset].chars().count();
+        }
+    }
+    if !people.is_empty() {
+        groups.push(Group { people });
+    }
+    (groups, diagnostics)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Group {
+    people: Vec<Answers>,
+}
+
+impl FromStr for Group {
+    type Err = Diagnostic;
+
+    /// Parses a single group, failing on the first problem
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut groups, diagnostics) = parse_input(s);
+        if let Some(diagnostic) = diagnostics.first() {
+            return Err(*diagnostic);
+        }
+        match (groups.pop(), groups.is_empty()) {
+            (Some(group), true) => Ok(group),
+            (None, _) => Err(Diagnostic {
+                line: 1,
+                col: 1,
+                problem: Problem::EmptyGroup,
+            }),
+            (Some(_), false) => {
+                let line = s.lines().position(|l| l.trim().is_empty()).unwrap_or(0);
+                Err(Diagnostic {
+                    line: line + 1,
+                    col: 1,
+                    problem: Problem::SplitGroup,
+                })
             }
-            if every {
-                count += 1
+        }
+    }
+}
+
+impl Group {
+    /// Combines every person's answers with `f`, an empty group has no answers.
+    pub fn fold(&self, f: impl Fn(Answers, Answers) -> Answers) -> Answers {
+        self.people.iter().copied().reduce(f).unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.people.len()
+    }
+
+    /// How many people answered each question, `a` first
+    pub fn question_counts(&self) -> [usize; 26] {
+        let mut counts = [0; 26];
+        for answers in &self.people {
+            for c in answers.iter() {
+                counts[(c as u8 - b'a') as usize] += 1;
             }
         }
-        sum += count
+        counts
     }
-    sum
+
+    pub fn any(&self) -> Answers {
+        self.fold(Answers::union)
+    }
+
+    pub fn every(&self) -> Answers {
+        self.fold(Answers::intersection)
+    }
+}
+
+fn count_answered_any(groups: &[Group]) -> usize {
+    groups.iter().map(|g| g.any().len()).sum()
+}
+
+fn count_answered_every(groups: &[Group]) -> usize {
+    groups.iter().map(|g| g.every().len()).sum()
+}
+
+/// Groups of one, three, two, four and one people
+#[cfg(test)]
+fn test_groups() -> Vec<Group> {
+    ["abc", "a\nb\nc", "ab\nac", "a\na\na\na", "b"]
+        .iter()
+        .map(|g| g.parse().unwrap())
+        .collect()
 }
 
 #[test]
@@ -59,7 +254,8 @@ fn test_count_answered_any() {
         b
     ";
 
-    let groups: Vec<&str> = input.split("\n\n").collect();
+    let (groups, diagnostics) = parse_input(input);
+    assert!(diagnostics.is_empty());
     let count = count_answered_any(&groups);
     assert_eq!(count, 11);
 }
@@ -84,8 +280,94 @@ fn test_count_answered_every() {
         b
     ";
 
-    let groups: Vec<&str> = input.split("\n\n").map(|g| g.trim()).collect();
-    println!("groups: {:#?}", groups);
+    let (groups, diagnostics) = parse_input(input);
+    assert!(diagnostics.is_empty());
     let count = count_answered_every(&groups);
     assert_eq!(count, 6);
 }
+
+#[test]
+fn test_group_fold() {
+    let group: Group = "abcx\nabcy\nabcz".parse().unwrap();
+    assert_eq!(group.any().to_string(), "abcxyz");
+    assert_eq!(group.every().to_string(), "abc");
+    assert_eq!(
+        group.fold(Answers::symmetric_difference).to_string(),
+        "abcxyz"
+    );
+    assert_eq!(Group::default().every(), Answers::default());
+}
+
+#[test]
+fn test_invalid_chars() {
+    let (groups, diagnostics) = parse_input("ab\n  a1c\n\nxY z\n");
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].any().to_string(), "abc");
+    assert_eq!(groups[1].every().to_string(), "xz");
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic {
+                line: 2,
+                col: 4,
+                problem: Problem::InvalidChar('1')
+            },
+            Diagnostic {
+                line: 4,
+                col: 2,
+                problem: Problem::InvalidChar('Y')
+            },
+            Diagnostic {
+                line: 4,
+                col: 3,
+                problem: Problem::InvalidChar(' ')
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_duplicates() {
+    let (groups, diagnostics) = parse_input("aab\nab");
+    assert_eq!(count_answered_any(&groups), 2);
+    assert_eq!(count_answered_every(&groups), 2);
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic {
+            line: 1,
+            col: 2,
+            problem: Problem::DuplicateAnswer('a')
+        }]
+    );
+}
+
+#[test]
+fn test_empty_groups() {
+    let (groups, diagnostics) = parse_input("\n\na\n\n\n\nb\n\n\n");
+    assert_eq!(groups.len(), 2);
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic {
+            line: 5,
+            col: 1,
+            problem: Problem::EmptyGroup
+        }]
+    );
+
+    let (groups, diagnostics) = parse_input("");
+    assert!(groups.is_empty() && diagnostics.is_empty());
+    assert_eq!(count_answered_every(&groups), 0);
+    assert_eq!(Group::default().every().len(), 0);
+    assert_eq!(
+        "  \n".parse::<Group>().unwrap_err().problem,
+        Problem::EmptyGroup
+    );
+    assert_eq!(
+        "a\n\nb".parse::<Group>(),
+        Err(Diagnostic {
+            line: 2,
+            col: 1,
+            problem: Problem::SplitGroup
+        })
+    );
+}
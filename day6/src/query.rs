@@ -0,0 +1,319 @@
+//! Set expressions over the answers in a group.
+//!
+//! ```text
+//! query   := operand (('|' | '&' | '-' | '^') operand)*
+//! operand := atom ('@' group)?
+//! atom    := 'any' | 'every' | 'majority' | 'atleast' k | 'exactly' k | '(' query ')'
+//! ```
+//!
+//! Operators are left associative with equal precedence. `@n` evaluates its operand
+//! on the n-th group (counting from 1) instead of the current one, so
+//! `any@1 - any@2` is what group 1 answered but group 2 didn't.
+
+use std::{fmt, str::FromStr};
+
+use super::{Answers, Group};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Answered by at least k people
+    AtLeast(usize),
+    /// Answered by exactly k people
+    Exactly(usize),
+    /// Answered by more than half of the group
+    Majority,
+    Every,
+    Binary(Box<Query>, Op, Box<Query>),
+    /// The query on group `group`, counting from 1, written at `pos`
+    InGroup {
+        query: Box<Query>,
+        group: usize,
+        pos: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueryError {
+    pub pos: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.msg, self.pos)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+fn error<T>(pos: usize, msg: impl Into<String>) -> Result<T, QueryError> {
+    Err(QueryError {
+        pos,
+        msg: msg.into(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Number(usize),
+    Op(Op),
+    At,
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token<'_>)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '|' => Token::Op(Op::Union),
+            '&' => Token::Op(Op::Intersection),
+            '-' => Token::Op(Op::Difference),
+            '^' => Token::Op(Op::SymmetricDifference),
+            '@' => Token::At,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_alphanumeric() => {
+                let mut end = pos + c.len_utf8();
+                while let Some((i, c)) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let word = &s[pos..end];
+                if c.is_ascii_digit() {
+                    match word.parse() {
+                        Ok(n) => Token::Number(n),
+                        Err(_) => return error(pos, format!("invalid number `{}`", word)),
+                    }
+                } else {
+                    Token::Word(word)
+                }
+            }
+            c => return error(pos, format!("unexpected `{}`", c)),
+        };
+        tokens.push((pos, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn pos(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(pos, _)| *pos)
+    }
+
+    fn bump(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.next).map(|(_, t)| t.clone());
+        self.next += 1;
+        token
+    }
+
+    fn number(&mut self) -> Result<usize, QueryError> {
+        let pos = self.pos();
+        match self.bump() {
+            Some(Token::Number(n)) => Ok(n),
+            _ => error(pos, "expected a number"),
+        }
+    }
+
+    /// A number that's at least 1, with `msg` as the error for 0
+    fn positive(&mut self, msg: &str) -> Result<usize, QueryError> {
+        let pos = self.pos();
+        match self.number()? {
+            0 => error(pos, msg),
+            n => Ok(n),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, QueryError> {
+        let mut lhs = self.operand()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            self.bump();
+            let rhs = self.operand()?;
+            lhs = Query::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn operand(&mut self) -> Result<Query, QueryError> {
+        let atom = self.atom()?;
+        if self.peek() == Some(&Token::At) {
+            self.bump();
+            let pos = self.pos();
+            Ok(Query::InGroup {
+                query: Box::new(atom),
+                group: self.positive("groups are counted from 1")?,
+                pos,
+            })
+        } else {
+            Ok(atom)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Query, QueryError> {
+        let pos = self.pos();
+        match self.bump() {
+            Some(Token::Word("any")) => Ok(Query::AtLeast(1)),
+            Some(Token::Word("every")) => Ok(Query::Every),
+            Some(Token::Word("majority")) => Ok(Query::Majority),
+            Some(Token::Word("atleast")) => Ok(Query::AtLeast(
+                self.positive("atleast 0 is the same as any")?,
+            )),
+            Some(Token::Word("exactly")) => Ok(Query::Exactly(
+                self.positive("nothing is answered by exactly 0 people")?,
+            )),
+            Some(Token::Word(word)) => error(pos, format!("unknown query `{}`", word)),
+            Some(Token::Open) => {
+                let query = self.query()?;
+                let pos = self.pos();
+                match self.bump() {
+                    Some(Token::Close) => Ok(query),
+                    _ => error(pos, "expected `)`"),
+                }
+            }
+            Some(_) => error(pos, "expected a query"),
+            None => error(pos, "unexpected end of query"),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+            end: s.len(),
+        };
+        let query = parser.query()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(_) => error(parser.pos(), "expected an operator"),
+        }
+    }
+}
+
+impl Query {
+    /// Fails if the query refers to a group that doesn't exist
+    pub fn check(&self, groups: &[Group]) -> Result<(), QueryError> {
+        match self {
+            Query::Binary(lhs, _, rhs) => {
+                lhs.check(groups)?;
+                rhs.check(groups)
+            }
+            Query::InGroup { group, pos, .. } if *group > groups.len() => error(
+                *pos,
+                format!("there is no group {}, only {}", group, groups.len()),
+            ),
+            Query::InGroup { query, .. } => query.check(groups),
+            _ => Ok(()),
+        }
+    }
+
+    /// Evaluates the query on `groups[current]`, once it has passed `check`
+    pub fn eval(&self, groups: &[Group], current: usize) -> Answers {
+        let group = &groups[current];
+        let filter = |keep: &dyn Fn(usize) -> bool| {
+            let counts = group.question_counts();
+            let mut answers = Answers::default();
+            for (c, count) in ('a'..='z').zip(counts.iter()) {
+                if keep(*count) {
+                    answers.insert(c);
+                }
+            }
+            answers
+        };
+        match self {
+            Query::AtLeast(k) => filter(&|count| count >= *k),
+            Query::Exactly(k) => filter(&|count| count == *k),
+            Query::Majority => filter(&|count| count > 0 && count * 2 > group.len()),
+            Query::Every => group.every(),
+            Query::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(groups, current), rhs.eval(groups, current));
+                match op {
+                    Op::Union => lhs | rhs,
+                    Op::Intersection => lhs & rhs,
+                    Op::Difference => lhs - rhs,
+                    Op::SymmetricDifference => lhs ^ rhs,
+                }
+            }
+            Query::InGroup { query, group, .. } => query.eval(groups, group - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+fn eval_all(query: &str) -> Vec<String> {
+    let groups = super::test_groups();
+    let query: Query = query.parse().unwrap();
+    query.check(&groups).unwrap();
+    (0..groups.len())
+        .map(|i| query.eval(&groups, i).to_string())
+        .collect()
+}
+
+#[test]
+fn test_queries() {
+    assert_eq!(eval_all("any"), vec!["abc", "abc", "abc", "a", "b"]);
+    assert_eq!(eval_all("every"), vec!["abc", "", "a", "a", "b"]);
+    assert_eq!(eval_all("exactly 1"), vec!["abc", "abc", "bc", "", "b"]);
+    assert_eq!(eval_all("atleast 2"), vec!["", "", "a", "a", ""]);
+    assert_eq!(eval_all("majority"), vec!["abc", "", "a", "a", "b"]);
+    assert_eq!(eval_all("any - every"), vec!["", "abc", "bc", "", ""]);
+    assert_eq!(eval_all("any@3 - any@4"), vec!["bc"; 5]);
+    assert_eq!(
+        eval_all("(any ^ any@5) & atleast 1"),
+        vec!["ac", "ac", "ac", "a", ""]
+    );
+}
+
+#[test]
+fn test_query_errors() {
+    let err = |s: &str| s.parse::<Query>().unwrap_err();
+    assert_eq!(err("any |").pos, 5);
+    assert_eq!(err("atleast x").pos, 8);
+    assert_eq!(err("(any").pos, 4);
+    assert_eq!(err("any every").pos, 4);
+    assert_eq!(err("anyone").msg, "unknown query `anyone`");
+    assert_eq!(err("any@0").pos, 4);
+    assert_eq!(err("any + every").pos, 4);
+    assert_eq!(err("atleast 0").pos, 8);
+    assert_eq!(
+        err("any | exactly 0").msg,
+        "nothing is answered by exactly 0 people"
+    );
+
+    let query: Query = "any | every@6".parse().unwrap();
+    assert_eq!(
+        query.check(&super::test_groups()),
+        Err(QueryError {
+            pos: 12,
+            msg: "there is no group 6, only 5".to_string()
+        })
+    );
+}
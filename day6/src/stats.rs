@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use super::Group;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow::anyhow!("expected table, csv or json, got `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuestionStats {
+    /// Groups where anyone answered the question
+    pub groups: usize,
+    pub people: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub groups: usize,
+    pub people: usize,
+    pub questions: [QuestionStats; 26],
+    /// How many groups there are of each size
+    pub group_sizes: BTreeMap<usize, usize>,
+}
+
+impl Stats {
+    pub fn new(groups: &[Group]) -> Stats {
+        let mut stats = Stats {
+            groups: groups.len(),
+            ..Stats::default()
+        };
+        for group in groups {
+            stats.people += group.len();
+            *stats.group_sizes.entry(group.len()).or_insert(0) += 1;
+            for (question, count) in stats.questions.iter_mut().zip(&group.question_counts()) {
+                if *count > 0 {
+                    question.groups += 1;
+                }
+                question.people += count;
+            }
+        }
+        stats
+    }
+
+    /// The share of all people who answered the question
+    pub fn rate(&self, question: usize) -> f64 {
+        if self.people == 0 {
+            0.0
+        } else {
+            self.questions[question].people as f64 / self.people as f64
+        }
+    }
+
+    fn letters(&self) -> impl Iterator<Item = (char, &QuestionStats)> {
+        ('a'..='z').zip(self.questions.iter())
+    }
+
+    pub fn write<W: Write>(&self, format: Format, out: &mut W) -> io::Result<()> {
+        match format {
+            Format::Table => self.write_table(out),
+            Format::Csv => self.write_csv(out),
+            Format::Json => self.write_json(out),
+        }
+    }
+
+    fn write_table<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{} groups, {} people", self.groups, self.people)?;
+        writeln!(out)?;
+        writeln!(out, "question  groups  people    rate")?;
+        for (i, (c, q)) in self.letters().enumerate() {
+            writeln!(
+                out,
+                "{:>8}  {:>6}  {:>6}  {:>6.2}%",
+                c,
+                q.groups,
+                q.people,
+                self.rate(i) * 100.0
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "group size  groups")?;
+        for (size, count) in &self.group_sizes {
+            writeln!(out, "{:>10}  {:>6}", size, count)?;
+        }
+        Ok(())
+    }
+
+    /// Two tables separated by a blank line: per question, then group sizes
+    fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "question,groups,people,rate")?;
+        for (i, (c, q)) in self.letters().enumerate() {
+            writeln!(out, "{},{},{},{:.4}", c, q.groups, q.people, self.rate(i))?;
+        }
+        writeln!(out)?;
+        writeln!(out, "group_size,groups")?;
+        for (size, count) in &self.group_sizes {
+            writeln!(out, "{},{}", size, count)?;
+        }
+        Ok(())
+    }
+
+    fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let questions: Vec<String> = self
+            .letters()
+            .enumerate()
+            .map(|(i, (c, q))| {
+                format!(
+                    "{{\"question\":\"{}\",\"groups\":{},\"people\":{},\"rate\":{:.4}}}",
+                    c,
+                    q.groups,
+                    q.people,
+                    self.rate(i)
+                )
+            })
+            .collect();
+        let sizes: Vec<String> = self
+            .group_sizes
+            .iter()
+            .map(|(size, count)| format!("{{\"size\":{},\"groups\":{}}}", size, count))
+            .collect();
+        writeln!(
+            out,
+            "{{\"groups\":{},\"people\":{},\"questions\":[{}],\"group_sizes\":[{}]}}",
+            self.groups,
+            self.people,
+            questions.join(","),
+            sizes.join(",")
+        )
+    }
+}
+
+#[test]
+fn test_counts() {
+    let stats = Stats::new(&super::test_groups());
+    assert_eq!((stats.groups, stats.people), (5, 11));
+    assert_eq!(
+        stats.questions[0],
+        QuestionStats {
+            groups: 4,
+            people: 8
+        }
+    );
+    assert_eq!(
+        stats.questions[1],
+        QuestionStats {
+            groups: 4,
+            people: 4
+        }
+    );
+    assert_eq!(
+        stats.questions[2],
+        QuestionStats {
+            groups: 3,
+            people: 3
+        }
+    );
+    assert_eq!(stats.questions[3], QuestionStats::default());
+    assert_eq!(
+        stats.group_sizes.into_iter().collect::<Vec<_>>(),
+        vec![(1, 2), (2, 1), (3, 1), (4, 1)]
+    );
+    assert_eq!(Stats::new(&[]).rate(0), 0.0);
+}
+
+#[test]
+fn test_csv() {
+    let mut out = Vec::new();
+    Stats::new(&super::test_groups())
+        .write(Format::Csv, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "question,groups,people,rate");
+    assert_eq!(lines[1], "a,4,8,0.7273");
+    assert_eq!(lines[28], "group_size,groups");
+    assert_eq!(&lines[29..], ["1,2", "2,1", "3,1", "4,1"]);
+}
+
+#[test]
+fn test_json() {
+    let mut out = Vec::new();
+    Stats::new(&super::test_groups())
+        .write(Format::Json, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with(
+        "{\"groups\":5,\"people\":11,\"questions\":[\
+         {\"question\":\"a\",\"groups\":4,\"people\":8,\"rate\":0.7273},"
+    ));
+    assert!(out.ends_with(
+        "\"group_sizes\":[{\"size\":1,\"groups\":2},{\"size\":2,\"groups\":1},\
+         {\"size\":3,\"groups\":1},{\"size\":4,\"groups\":1}]}\n"
+    ));
+}
@@ -0,0 +1,227 @@
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+use super::graph::{BagGraph, BagId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow::anyhow!("expected dot or json, got `{}`", s)),
+        }
+    }
+}
+
+/// Which part of the graph to export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subgraph {
+    All,
+    /// The bag and everything inside it
+    From(BagId),
+    /// The bag and everything that can hold it
+    To(BagId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub subgraph: Subgraph,
+    /// Highlighted in DOT output
+    pub highlight: Option<BagId>,
+    /// Colour the bags holding and held by the highlighted bag in DOT output
+    pub relatives: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            subgraph: Subgraph::All,
+            highlight: None,
+            relatives: false,
+        }
+    }
+}
+
+fn included(graph: &BagGraph, subgraph: Subgraph) -> Vec<bool> {
+    match subgraph {
+        Subgraph::All => vec![true; graph.len()],
+        Subgraph::From(id) => {
+            let mut included = graph.descendants(id);
+            included[id] = true;
+            included
+        }
+        Subgraph::To(id) => {
+            let mut included = graph.ancestors(id);
+            included[id] = true;
+            included
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn write<W: Write>(
+    graph: &BagGraph,
+    format: Format,
+    options: Options,
+    out: &mut W,
+) -> io::Result<()> {
+    match format {
+        Format::Dot => write_dot(graph, options, out),
+        Format::Json => write_json(graph, options, out),
+    }
+}
+
+/// Edges point from the outer bag to the bags inside it and are labelled with counts.
+pub fn write_dot<W: Write>(graph: &BagGraph, options: Options, out: &mut W) -> io::Result<()> {
+    let included = included(graph, options.subgraph);
+    let (ancestors, descendants) = match options.highlight {
+        Some(id) if options.relatives => (graph.ancestors(id), graph.descendants(id)),
+        _ => (vec![false; graph.len()], vec![false; graph.len()]),
+    };
+    writeln!(out, "digraph bags {{")?;
+    for bag in graph.ids().filter(|b| included[*b]) {
+        let color = if options.highlight == Some(bag) {
+            Some("gold")
+        } else if ancestors[bag] {
+            Some("lightblue")
+        } else if descendants[bag] {
+            Some("palegreen")
+        } else {
+            None
+        };
+        match color {
+            Some(color) => writeln!(
+                out,
+                "    \"{}\" [style=filled, fillcolor={}];",
+                escape(graph.name(bag)),
+                color
+            )?,
+            None => writeln!(out, "    \"{}\";", escape(graph.name(bag)))?,
+        }
+    }
+    for bag in graph.ids().filter(|b| included[*b]) {
+        for edge in graph.contains(bag).iter().filter(|e| included[e.bag]) {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label={}];",
+                escape(graph.name(bag)),
+                escape(graph.name(edge.bag)),
+                edge.count
+            )?;
+        }
+    }
+    writeln!(out, "}}")
+}
+
+/// An object mapping every bag to the bags directly inside it, with counts.
+pub fn write_json<W: Write>(graph: &BagGraph, options: Options, out: &mut W) -> io::Result<()> {
+    let included = included(graph, options.subgraph);
+    let bags: Vec<String> = graph
+        .ids()
+        .filter(|b| included[*b])
+        .map(|bag| {
+            let edges: Vec<String> = graph
+                .contains(bag)
+                .iter()
+                .filter(|e| included[e.bag])
+                .map(|e| {
+                    format!(
+                        "{{\"bag\":\"{}\",\"count\":{}}}",
+                        escape(graph.name(e.bag)),
+                        e.count
+                    )
+                })
+                .collect();
+            format!("\"{}\":[{}]", escape(graph.name(bag)), edges.join(","))
+        })
+        .collect();
+    writeln!(out, "{{{}}}", bags.join(","))
+}
+
+#[cfg(test)]
+fn quoted_graph() -> BagGraph {
+    let mut graph = BagGraph::default();
+    let (red, gold, blue) = (
+        graph.intern("light red"),
+        graph.intern("shiny gold"),
+        graph.intern("faded \"blue\""),
+    );
+    let black = graph.intern("dotted black");
+    graph.add_rule(red, 2, gold);
+    graph.add_rule(gold, 3, blue);
+    graph.add_rule(red, 1, black);
+    graph
+}
+
+#[cfg(test)]
+fn export(format: Format, options: Options) -> String {
+    let mut out = Vec::new();
+    write(&quoted_graph(), format, options, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_dot() {
+    let options = Options {
+        subgraph: Subgraph::All,
+        highlight: Some(1),
+        relatives: true,
+    };
+    assert_eq!(
+        export(Format::Dot, options),
+        r#"digraph bags {
+    "light red" [style=filled, fillcolor=lightblue];
+    "shiny gold" [style=filled, fillcolor=gold];
+    "faded \"blue\"" [style=filled, fillcolor=palegreen];
+    "dotted black";
+    "light red" -> "shiny gold" [label=2];
+    "light red" -> "dotted black" [label=1];
+    "shiny gold" -> "faded \"blue\"" [label=3];
+}
+"#
+    );
+}
+
+#[test]
+fn test_subgraphs() {
+    let from = Options {
+        subgraph: Subgraph::From(1),
+        ..Options::default()
+    };
+    assert_eq!(
+        export(Format::Dot, from),
+        "digraph bags {\n    \"shiny gold\";\n    \"faded \\\"blue\\\"\";\n    \
+         \"shiny gold\" -> \"faded \\\"blue\\\"\" [label=3];\n}\n"
+    );
+    let to = Options {
+        subgraph: Subgraph::To(1),
+        ..Options::default()
+    };
+    assert_eq!(
+        export(Format::Json, to),
+        "{\"light red\":[{\"bag\":\"shiny gold\",\"count\":2}],\"shiny gold\":[]}\n"
+    );
+}
+
+#[test]
+fn test_json() {
+    assert_eq!(
+        export(Format::Json, Options::default()),
+        "{\"light red\":[{\"bag\":\"shiny gold\",\"count\":2},{\"bag\":\"dotted black\",\"count\":1}],\
+         \"shiny gold\":[{\"bag\":\"faded \\\"blue\\\"\",\"count\":3}],\
+         \"faded \\\"blue\\\"\":[],\"dotted black\":[]}\n"
+    );
+}
@@ -0,0 +1,392 @@
+use std::{collections::HashMap, fmt};
+
+pub type BagId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub bag: BagId,
+    pub count: u32,
+}
+
+/// Bag rules with interned colour names. Every rule is stored both ways: which bags a
+/// bag contains, and which bags it is contained by.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BagGraph {
+    ids: HashMap<String, BagId>,
+    names: Vec<String>,
+    contains: Vec<Vec<Edge>>,
+    contained_by: Vec<Vec<Edge>>,
+}
+
+impl BagGraph {
+    /// Returns the id of `name`, adding a bag without any rules if it's new.
+    pub fn intern(&mut self, name: &str) -> BagId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.ids.insert(name.to_string(), id);
+        self.names.push(name.to_string());
+        self.contains.push(Vec::new());
+        self.contained_by.push(Vec::new());
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<BagId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: BagId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = BagId> {
+        0..self.len()
+    }
+
+    /// `outer` must directly contain `count` of `inner`
+    pub fn add_rule(&mut self, outer: BagId, count: u32, inner: BagId) {
+        self.contains[outer].push(Edge { bag: inner, count });
+        self.contained_by[inner].push(Edge { bag: outer, count });
+    }
+
+    pub fn contains(&self, id: BagId) -> &[Edge] {
+        &self.contains[id]
+    }
+
+    pub fn contained_by(&self, id: BagId) -> &[Edge] {
+        &self.contained_by[id]
+    }
+
+    /// Marks every bag reachable from `id` along `edges`, not counting `id` itself
+    /// unless it's on a cycle.
+    fn reachable<'a>(&self, id: BagId, edges: impl Fn(BagId) -> &'a [Edge]) -> Vec<bool> {
+        let mut seen = vec![false; self.len()];
+        let mut queue = vec![id];
+        while let Some(bag) = queue.pop() {
+            for edge in edges(bag) {
+                if !seen[edge.bag] {
+                    seen[edge.bag] = true;
+                    queue.push(edge.bag);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Every bag `id` holds at any depth
+    pub fn descendants(&self, id: BagId) -> Vec<bool> {
+        self.reachable(id, |bag| self.contains(bag))
+    }
+
+    /// Every bag that holds `id` at any depth
+    pub fn ancestors(&self, id: BagId) -> Vec<bool> {
+        self.reachable(id, |bag| self.contained_by(bag))
+    }
+
+    /// Every bag, each one after all the bags it contains. Fails on the first cycle
+    /// found.
+    pub fn topological_order(&self) -> Result<Vec<BagId>, GraphError> {
+        self.walk(self.ids(), |bag| self.contains(bag))
+    }
+
+    /// `outer` and every bag inside it, each one after all the bags it contains. Fails
+    /// on the first cycle found, but only cycles inside `outer` count.
+    pub fn topological_order_from(&self, outer: BagId) -> Result<Vec<BagId>, GraphError> {
+        self.walk(Some(outer), |bag| self.contains(bag))
+    }
+
+    /// `inner` and every bag that holds it, each one after all the bags it's inside.
+    /// Fails on the first cycle found, but only cycles around `inner` count.
+    pub fn topological_order_to(&self, inner: BagId) -> Result<Vec<BagId>, GraphError> {
+        self.walk(Some(inner), |bag| self.contained_by(bag))
+    }
+
+    /// Visits every bag reachable from `roots` along `edges`, each one after all the
+    /// bags reachable from it. The walk keeps its own stack, so deep nesting can't
+    /// overflow, and fails with the path of the first cycle it finds.
+    fn walk<'a>(
+        &self,
+        roots: impl IntoIterator<Item = BagId>,
+        edges: impl Fn(BagId) -> &'a [Edge],
+    ) -> Result<Vec<BagId>, GraphError> {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum State {
+            New,
+            Open,
+            Done,
+        }
+
+        let mut state = vec![State::New; self.len()];
+        let mut order = Vec::new();
+        // Each open bag with the index of the next edge to follow
+        let mut stack: Vec<(BagId, usize)> = Vec::new();
+        for root in roots {
+            if state[root] != State::New {
+                continue;
+            }
+            state[root] = State::Open;
+            stack.push((root, 0));
+            while let Some((bag, edge)) = stack.last_mut() {
+                let bag = *bag;
+                match edges(bag).get(*edge) {
+                    Some(Edge { bag: next, .. }) => {
+                        *edge += 1;
+                        match state[*next] {
+                            State::New => {
+                                state[*next] = State::Open;
+                                stack.push((*next, 0));
+                            }
+                            State::Open => {
+                                let start = stack.iter().position(|(b, _)| b == next).unwrap();
+                                let mut path: Vec<String> = stack[start..]
+                                    .iter()
+                                    .map(|(b, _)| self.name(*b).to_string())
+                                    .collect();
+                                path.push(self.name(*next).to_string());
+                                return Err(GraphError::Cycle(path));
+                            }
+                            State::Done => {}
+                        }
+                    }
+                    None => {
+                        state[bag] = State::Done;
+                        order.push(bag);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// For every bag, whether it holds `inner` at any depth
+    pub fn can_contain_all(&self, inner: BagId) -> Result<Vec<bool>, GraphError> {
+        let mut can_contain = vec![false; self.len()];
+        // Everything above `inner` holds it
+        for bag in self.topological_order_to(inner)? {
+            can_contain[bag] = bag != inner;
+        }
+        Ok(can_contain)
+    }
+
+    /// For `outer` and every bag inside it, the number of bags inside that one, not
+    /// counting itself. Bags outside `outer` are left at 0.
+    pub fn count_contained(&self, outer: BagId) -> Result<Vec<u64>, GraphError> {
+        let mut counts = vec![0u64; self.len()];
+        for bag in self.topological_order_from(outer)? {
+            let mut sum: u64 = 0;
+            for edge in self.contains(bag) {
+                sum = counts[edge.bag]
+                    .checked_add(1)
+                    .and_then(|inside| inside.checked_mul(edge.count as u64))
+                    .and_then(|bags| bags.checked_add(sum))
+                    .ok_or_else(|| GraphError::Overflow(self.name(bag).to_string()))?;
+            }
+            counts[bag] = sum;
+        }
+        Ok(counts)
+    }
+
+    /// The most levels of bags nested inside `outer`. A bag holding nothing has depth
+    /// 0.
+    pub fn depth(&self, outer: BagId) -> Result<usize, GraphError> {
+        let mut depths = vec![0; self.len()];
+        for bag in self.topological_order_from(outer)? {
+            depths[bag] = self
+                .contains(bag)
+                .iter()
+                .map(|e| depths[e.bag] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+        Ok(depths[outer])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// Bag names along the cycle, starting and ending with the same bag
+    Cycle(Vec<String>),
+    /// The bag whose count doesn't fit in a u64
+    Overflow(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Cycle(path) => write!(f, "bags contain each other: {}", path.join(" -> ")),
+            GraphError::Overflow(bag) => write!(f, "too many bags inside {}", bag),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl fmt::Display for BagGraph {
+    /// Writes the rules back in the puzzle's format, one bag per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for id in self.ids() {
+            write!(f, "{} bags contain ", self.name(id))?;
+            if self.contains(id).is_empty() {
+                write!(f, "no other bags")?;
+            }
+            for (i, edge) in self.contains(id).iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                let plural = if edge.count == 1 { "" } else { "s" };
+                write!(f, "{} {} bag{}", edge.count, self.name(edge.bag), plural)?;
+            }
+            writeln!(f, ".")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_intern() {
+    let mut graph = BagGraph::default();
+    let gold = graph.intern("shiny gold");
+    let olive = graph.intern("dark olive");
+    assert_eq!(graph.intern("shiny gold"), gold);
+    assert_eq!(graph.id("dark olive"), Some(olive));
+    assert_eq!(graph.id("faded blue"), None);
+    assert_eq!(graph.name(olive), "dark olive");
+    assert_eq!(graph.len(), 2);
+}
+
+#[test]
+fn test_edges() {
+    let mut graph = BagGraph::default();
+    let (red, white, gold) = (
+        graph.intern("red"),
+        graph.intern("white"),
+        graph.intern("gold"),
+    );
+    graph.add_rule(red, 1, white);
+    graph.add_rule(red, 2, gold);
+    graph.add_rule(white, 3, gold);
+    assert_eq!(
+        graph.contains(red),
+        &[
+            Edge {
+                bag: white,
+                count: 1
+            },
+            Edge {
+                bag: gold,
+                count: 2
+            }
+        ]
+    );
+    assert_eq!(
+        graph.contained_by(gold),
+        &[
+            Edge { bag: red, count: 2 },
+            Edge {
+                bag: white,
+                count: 3
+            }
+        ]
+    );
+    assert!(graph.contained_by(red).is_empty());
+}
+
+#[test]
+fn test_reachable() {
+    let graph = chain(4, 1);
+    assert_eq!(graph.descendants(1), vec![false, false, true, true]);
+    assert_eq!(graph.ancestors(1), vec![true, false, false, false]);
+}
+
+#[test]
+fn test_depth() {
+    let mut graph = chain(4, 1);
+    graph.add_rule(0, 1, 3);
+    assert_eq!(graph.depth(0), Ok(3));
+    assert_eq!(graph.depth(3), Ok(0));
+}
+
+#[test]
+fn test_cycle() {
+    let mut graph = BagGraph::default();
+    let (a, b, c, d) = (
+        graph.intern("a"),
+        graph.intern("b"),
+        graph.intern("c"),
+        graph.intern("d"),
+    );
+    graph.add_rule(a, 1, b);
+    graph.add_rule(b, 1, c);
+    graph.add_rule(c, 1, d);
+    assert_eq!(graph.topological_order(), Ok(vec![d, c, b, a]));
+    graph.add_rule(d, 2, b);
+    let cycle = GraphError::Cycle(
+        vec!["b", "c", "d", "b"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    );
+    assert_eq!(graph.topological_order(), Err(cycle));
+    assert!(graph.count_contained(a).is_err());
+
+    // Cycles the bag can't reach don't matter
+    let mut graph = BagGraph::default();
+    let (a, b, c, d) = (
+        graph.intern("a"),
+        graph.intern("b"),
+        graph.intern("c"),
+        graph.intern("d"),
+    );
+    graph.add_rule(a, 2, b);
+    graph.add_rule(c, 1, d);
+    graph.add_rule(d, 1, c);
+    assert_eq!(graph.topological_order_from(a), Ok(vec![b, a]));
+    assert_eq!(graph.count_contained(a), Ok(vec![2, 0, 0, 0]));
+    assert_eq!(graph.depth(a), Ok(1));
+    assert!(graph.count_contained(c).is_err());
+
+    let mut graph = BagGraph::default();
+    let a = graph.intern("a");
+    graph.add_rule(a, 1, a);
+    assert_eq!(
+        graph.can_contain_all(a),
+        Err(GraphError::Cycle(vec!["a".to_string(), "a".to_string()]))
+    );
+}
+
+#[cfg(test)]
+fn chain(len: usize, count: u32) -> BagGraph {
+    let mut graph = BagGraph::default();
+    let ids: Vec<BagId> = (0..len).map(|i| graph.intern(&i.to_string())).collect();
+    for pair in ids.windows(2) {
+        graph.add_rule(pair[0], count, pair[1]);
+    }
+    graph
+}
+
+#[test]
+fn test_overflow() {
+    // (max + 1) * max fits, one more level doesn't
+    assert_eq!(
+        chain(3, u32::MAX).count_contained(0).unwrap()[0],
+        (u32::MAX as u64 + 1) * u32::MAX as u64
+    );
+    assert_eq!(
+        chain(4, u32::MAX).count_contained(0),
+        Err(GraphError::Overflow("0".to_string()))
+    );
+}
+
+#[test]
+fn test_deep_nesting() {
+    let graph = chain(100_000, 1);
+    assert_eq!(graph.count_contained(0).unwrap()[0], 99_999);
+    let can_contain = graph.can_contain_all(99_999).unwrap();
+    assert!(can_contain[0] && !can_contain[99_999]);
+}
@@ -1,121 +1,156 @@
-use std::ops::Deref;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
 
 use anyhow::Result;
+use num_bigint::BigUint;
 
-pub const MAX_CONTAINED_BAGS: usize = 8;
+mod export;
+mod graph;
+mod pack;
+mod query;
+mod rules;
+
+use graph::{BagGraph, BagId};
+use rules::parse_bag_rules;
 
 fn main() -> Result<()> {
     let input: Vec<String> = input::read_lines("inputs/7")?;
-    let bag_names = get_bag_names(&input);
-    let bags = parse_bag_rules(&input, &bag_names);
+    let graph = parse_bag_rules(&input)?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((cmd, args)) = args.split_first() {
+        return match cmd.as_str() {
+            "export" => export(&graph, args),
+            "query" => query(&graph, args),
+            "pack" => pack(&graph, args),
+            _ => anyhow::bail!(USAGE),
+        };
+    }
 
-    let bag_id = bag_names.iter().position(|c| c == "shiny gold").unwrap();
+    let bag_id = bag(&graph, "shiny gold")?;
 
-    let count = bags.iter().filter(|b| b.can_contain(bag_id, &bags)).count();
+    let count = graph
+        .can_contain_all(bag_id)?
+        .iter()
+        .filter(|c| **c)
+        .count();
     println!("part 1: {}", count);
 
-    // count_contained adds the containing (top-level) bag too
-    let count = bags.get(bag_id).unwrap().count_contained(&bags) - 1;
-    println!("part 2: {}", count);
+    // Fails if shiny gold ends up inside itself
+    let counts = graph.count_contained(bag_id)?;
+    println!("part 2: {}", counts[bag_id]);
     Ok(())
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct ContainmentRule {
-    pub count: u8,
-    pub id: usize,
-}
+const USAGE: &str = "usage: day7
+       day7 export <dot|json> [--from <bag> | --to <bag>] [--highlight <bag>] [--relatives]
+       day7 query [<query>]
+       day7 pack budget <bags>
+       day7 pack available <count> <bag>[, <count> <bag>...]
+       day7 pack <depth|total> <bag>
+
+queries: containers-of <bag>, contents-of <bag>, path <bag> -> <bag>, depth <bag>,
+         roots, leaves
+Without a query, day7 reads one per line from stdin.";
 
-#[derive(Debug, PartialEq, Eq)]
-struct Bag {
-    pub id: usize,
-    pub contains: [Option<ContainmentRule>; 8],
+fn bag(graph: &BagGraph, name: &str) -> Result<BagId> {
+    graph
+        .id(name)
+        .ok_or_else(|| anyhow::anyhow!("there are no {} bags", name))
 }
 
-impl Bag {
-    pub fn can_contain(&self, bag_id: usize, bags: &[Bag]) -> bool {
-        if self
-            .contains
-            .iter()
-            .any(|c| c.is_some() && c.unwrap().id == bag_id)
-        {
-            true
-        } else {
-            for rule in self.contains.iter() {
-                if let Some(ContainmentRule {
-                    id: contained_id, ..
-                }) = rule
-                {
-                    if bags[*contained_id].can_contain(bag_id, bags) {
-                        return true;
-                    }
-                }
+fn export(graph: &BagGraph, args: &[String]) -> Result<()> {
+    let (format, mut args) = match args.split_first() {
+        Some((format, args)) => (format.parse()?, args.iter()),
+        None => anyhow::bail!(USAGE),
+    };
+    let mut options = export::Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = || -> Result<BagId> {
+            match args.next() {
+                Some(name) => bag(graph, name),
+                None => anyhow::bail!("{} needs a bag", arg),
             }
-            false
+        };
+        match arg.as_str() {
+            "--from" => options.subgraph = export::Subgraph::From(value()?),
+            "--to" => options.subgraph = export::Subgraph::To(value()?),
+            "--highlight" => options.highlight = Some(value()?),
+            "--relatives" => options.relatives = true,
+            _ => anyhow::bail!(USAGE),
         }
     }
-    pub fn count_contained(&self, bags: &[Bag]) -> usize {
-        let mut sum = 1;
-        for rule in self.contains.iter() {
-            if let Some(ContainmentRule { id, count }) = rule {
-                sum += *count as usize * bags[*id].count_contained(bags)
-            }
-        }
-        sum
+    // Highlight the bag the export is centred on unless told otherwise
+    if let (None, export::Subgraph::From(id) | export::Subgraph::To(id)) =
+        (options.highlight, options.subgraph)
+    {
+        options.highlight = Some(id);
     }
+    export::write(graph, format, options, &mut std::io::stdout())?;
+    Ok(())
 }
 
-fn get_bag_names<T: Deref<Target = str>>(input: &[T]) -> Vec<String> {
-    let mut names = Vec::new();
-    for line in input {
-        // Take the first two words
-        let second_space = line.match_indices(' ').nth(1).unwrap().0;
-        let bag_name = &line[0..second_space];
-        names.push(bag_name.to_string());
+fn pack(graph: &BagGraph, args: &[String]) -> Result<()> {
+    let (cmd, rest) = match args.split_first() {
+        Some((cmd, rest)) if !rest.is_empty() => (cmd, rest.join(" ")),
+        _ => anyhow::bail!(USAGE),
+    };
+    match cmd.as_str() {
+        "budget" => {
+            let budget: BigUint = rest.parse()?;
+            for (bag, total) in pack::within_budget(graph, &budget)? {
+                println!("{} {}", total, graph.name(bag));
+            }
+        }
+        "available" => {
+            let mut available = HashMap::new();
+            for item in rest.split(',') {
+                let (count, name) = match item.trim().split_once(' ') {
+                    Some((count, name)) => (count.parse::<BigUint>()?, name.trim()),
+                    None => anyhow::bail!("expected <count> <bag>, got `{}`", item.trim()),
+                };
+                *available.entry(bag(graph, name)?).or_default() += count;
+            }
+            for bag in pack::packable(graph, &available)? {
+                println!("{}", graph.name(bag));
+            }
+        }
+        "depth" => {
+            let (min, max) = pack::nesting_depth(graph, bag(graph, &rest)?)?;
+            println!("{} is nested {} to {} bags deep", rest, min, max);
+        }
+        "total" => {
+            let total = pack::total(graph, bag(graph, &rest)?)?;
+            println!("{} bags to carry {} fully packed", total, rest);
+        }
+        _ => anyhow::bail!(USAGE),
     }
-    names.shrink_to_fit();
-    names
+    Ok(())
 }
 
-fn parse_bag_rules<T: Deref<Target = str>>(input: &[T], names: &[String]) -> Vec<Bag> {
-    let mut bags = Vec::new();
-    for line in input {
-        let mut stream = uwl::Stream::new(line);
-        let second_space = line.match_indices(' ').nth(1).unwrap().0;
-        let name = stream.advance(second_space);
-        let id = names.iter().position(|s| s == name).unwrap();
-        // Eat the space + "bags contain" + space
-        stream.advance(14);
-        let mut containment_index = 0;
-        let mut rules: [Option<ContainmentRule>; 8] = [None; MAX_CONTAINED_BAGS];
-        while !stream.is_empty() {
-            let part = stream.peek_until(|c| c == b',' || c == b'.');
-            let rule = if part == "no other bags" {
-                None
-            } else {
-                let count = stream
-                    .take_while(|c| c.is_ascii_digit())
-                    .parse::<u8>()
-                    .unwrap();
-                stream.advance(1);
-                let second_space = stream.rest().match_indices(' ').nth(1).unwrap().0;
-                let name = stream.advance(second_space);
-                let id = names.iter().position(|s| s == name).unwrap();
-                Some(ContainmentRule { count, id })
-            };
-            rules[containment_index] = rule;
-            // Eat the space + "bags" + comma + space
-            stream.take_while(|c| c != b',');
-            stream.advance(2);
-            containment_index += 1;
+fn query(graph: &BagGraph, args: &[String]) -> Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match args {
+        [query] => query::run(graph, &query.parse()?, &mut out),
+        [] => {
+            // Keep going after a bad query so a typo doesn't end the session
+            for line in io::stdin().lock().lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if let Err(e) = line.parse().and_then(|q| query::run(graph, &q, &mut out)) {
+                    writeln!(out, "error: {}", e)?;
+                }
+                out.flush()?;
+            }
+            Ok(())
         }
-        bags.push(Bag {
-            id,
-            contains: rules,
-        })
+        _ => anyhow::bail!(USAGE),
     }
-    bags.sort_unstable_by_key(|c| c.id);
-    bags
 }
 
 #[cfg(test)]
@@ -130,101 +165,113 @@ vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
 
+#[cfg(test)]
+fn test_graph() -> BagGraph {
+    let lines: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
+    parse_bag_rules(&lines).unwrap()
+}
+
 #[test]
 fn test_id_map() {
-    let lines: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
-    let bag_names = get_bag_names(&lines);
-    assert_eq!(bag_names.len(), 9);
+    let graph = test_graph();
+    assert_eq!(graph.len(), 9);
+    let mut names: Vec<&str> = graph.ids().map(|id| graph.name(id)).collect();
+    names.sort_unstable();
     assert_eq!(
-        bag_names,
+        names,
         vec![
-            "light red",
-            "dark orange",
             "bright white",
+            "dark olive",
+            "dark orange",
+            "dotted black",
+            "faded blue",
+            "light red",
             "muted yellow",
             "shiny gold",
-            "dark olive",
             "vibrant plum",
-            "faded blue",
-            "dotted black"
         ]
     );
 }
 
 #[test]
 fn test_parse_bags() {
-    let lines: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
-    let bag_names = get_bag_names(&lines);
-    let bags = parse_bag_rules(&lines, &bag_names);
-    assert_eq!(
-        bags.get(0).unwrap(),
-        &Bag {
-            id: 0,
-            contains: [
-                Some(ContainmentRule { id: 2, count: 1 }),
-                Some(ContainmentRule { id: 3, count: 2 }),
-                None,
-                None,
-                None,
-                None,
-                None,
-                None
-            ]
-        }
-    );
+    let graph = test_graph();
+    let id = |name| graph.id(name).unwrap();
+    let contains = |name| -> Vec<(&str, u32)> {
+        graph
+            .contains(id(name))
+            .iter()
+            .map(|e| (graph.name(e.bag), e.count))
+            .collect()
+    };
     assert_eq!(
-        bags.get(2).unwrap(),
-        &Bag {
-            id: 2,
-            contains: [
-                Some(ContainmentRule { id: 4, count: 1 }),
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-                None
-            ]
-        }
+        contains("light red"),
+        vec![("bright white", 1), ("muted yellow", 2)]
     );
+    assert_eq!(contains("bright white"), vec![("shiny gold", 1)]);
+    assert_eq!(contains("dotted black"), vec![]);
+    let contained_by: Vec<&str> = graph
+        .contained_by(id("shiny gold"))
+        .iter()
+        .map(|e| graph.name(e.bag))
+        .collect();
+    assert_eq!(contained_by, vec!["bright white", "muted yellow"]);
+}
+
+#[test]
+fn test_display() {
+    let graph = test_graph();
+    let rules: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
+    // Bags are interned as they're first mentioned, not in rule order
+    let mut displayed: Vec<String> = graph.to_string().lines().map(String::from).collect();
+    displayed.sort_unstable();
+    let mut expected: Vec<&str> = rules.clone();
+    expected.sort_unstable();
+    assert_eq!(displayed, expected);
     assert_eq!(
-        bags.get(8).unwrap(),
-        &Bag {
-            id: 8,
-            contains: [None; 8]
-        }
+        parse_bag_rules(&rules).unwrap().to_string(),
+        graph.to_string()
     );
 }
 
 #[test]
 fn test_can_contain() {
-    let lines: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
-    let bag_names = get_bag_names(&lines);
-    let bags = parse_bag_rules(&lines, &bag_names);
-    assert!(bags[0].can_contain(2, &bags));
-    assert!(bags[0].can_contain(4, &bags));
-    assert!(bags[0].can_contain(7, &bags));
+    let graph = test_graph();
+    let id = |name| graph.id(name).unwrap();
+    let can_contain = |outer, inner| graph.can_contain_all(id(inner)).unwrap()[id(outer)];
+    assert!(can_contain("light red", "bright white"));
+    assert!(can_contain("light red", "shiny gold"));
+    assert!(can_contain("light red", "faded blue"));
+    assert!(!can_contain("faded blue", "light red"));
+    assert!(!can_contain("shiny gold", "shiny gold"));
 }
 
 #[test]
 fn test_count_contained() {
-    let lines: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
-    let bag_names = get_bag_names(&lines);
-    let bags = parse_bag_rules(&lines, &bag_names);
-    let bag_id = bag_names.iter().position(|c| c == "shiny gold").unwrap();
+    let graph = test_graph();
+    let bag_id = graph.id("shiny gold").unwrap();
+
+    let counts = graph.count_contained(bag_id).unwrap();
+    assert_eq!(counts[bag_id], 32);
+    assert_eq!(counts[graph.id("dark olive").unwrap()], 7);
 
-    assert_eq!(bags[bag_id].count_contained(&bags) - 1, 32);
+    // Only cycles inside shiny gold matter
+    let mut graph = test_graph();
+    let (blue, green) = (graph.intern("pale blue"), graph.intern("pale green"));
+    graph.add_rule(blue, 1, green);
+    graph.add_rule(green, 1, blue);
+    assert_eq!(graph.count_contained(bag_id).unwrap()[bag_id], 32);
+    graph.add_rule(graph.id("faded blue").unwrap(), 1, blue);
+    assert!(graph.count_contained(bag_id).is_err());
+    assert_eq!(counts[graph.id("faded blue").unwrap()], 0);
 }
 
 #[test]
 fn test_available_colors() {
     let test_color = "shiny gold";
-    let lines: Vec<&str> = TEST_INPUT.trim().lines().map(|l| l.trim()).collect();
-    let bag_names = get_bag_names(&lines);
-    let bags = parse_bag_rules(&lines, &bag_names);
-    let bag_id = bag_names.iter().position(|c| c == test_color).unwrap();
+    let graph = test_graph();
+    let bag_id = graph.id(test_color).unwrap();
 
-    let count = bags.iter().filter(|b| b.can_contain(bag_id, &bags)).count();
-    assert_eq!(count, 4);
+    let count = graph.can_contain_all(bag_id).unwrap();
+    assert_eq!(count.iter().filter(|c| **c).count(), 4);
 }
@@ -0,0 +1,238 @@
+//! Working back from the rules to what can be carried. Counts are exact, however
+//! many bags a rule set multiplies out to.
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+
+use super::graph::{BagGraph, BagId, GraphError};
+
+/// For every bag, the bags needed to carry it fully packed, counting itself
+pub fn totals(graph: &BagGraph) -> Result<Vec<BigUint>, GraphError> {
+    Ok(totals_along(graph, &graph.topological_order()?))
+}
+
+/// The bags needed to carry `outer` fully packed, counting itself. Only cycles inside
+/// `outer` are errors.
+pub fn total(graph: &BagGraph, outer: BagId) -> Result<BigUint, GraphError> {
+    let mut totals = totals_along(graph, &graph.topological_order_from(outer)?);
+    Ok(totals.swap_remove(outer))
+}
+
+/// Totals for the bags in `order`, which lists each bag after the bags it contains
+fn totals_along(graph: &BagGraph, order: &[BagId]) -> Vec<BigUint> {
+    let mut totals = vec![BigUint::default(); graph.len()];
+    for &bag in order {
+        let mut total = BigUint::from(1u32);
+        for edge in graph.contains(bag) {
+            total += &totals[edge.bag] * edge.count;
+        }
+        totals[bag] = total;
+    }
+    totals
+}
+
+/// Every bag that can be carried fully packed with at most `budget` bags, with how
+/// many it takes, fewest first
+pub fn within_budget(
+    graph: &BagGraph,
+    budget: &BigUint,
+) -> Result<Vec<(BagId, BigUint)>, GraphError> {
+    let mut fits: Vec<(BagId, BigUint)> = totals(graph)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, total)| total <= budget)
+        .collect();
+    fits.sort_by(|(a, a_total), (b, b_total)| {
+        a_total
+            .cmp(b_total)
+            .then_with(|| graph.name(*a).cmp(graph.name(*b)))
+    });
+    Ok(fits)
+}
+
+/// How many of each bag go inside `outer` when it's fully packed. `order` lists every
+/// bag inside `outer` after the bags it contains, as `topological_order` does.
+pub fn contents(graph: &BagGraph, outer: BagId, order: &[BagId]) -> Vec<BigUint> {
+    let mut counts = vec![BigUint::default(); graph.len()];
+    counts[outer] = BigUint::from(1u32);
+    // Outer bags first, so each bag's count is final before it's passed on
+    for &bag in order.iter().rev() {
+        if counts[bag] == BigUint::default() {
+            continue;
+        }
+        for edge in graph.contains(bag) {
+            let inside = &counts[bag] * edge.count;
+            counts[edge.bag] += inside;
+        }
+    }
+    counts[outer] = BigUint::default();
+    counts
+}
+
+/// Every bag that can be fully packed from the `available` inner bags, by name.
+/// Bags that aren't listed aren't available.
+pub fn packable(
+    graph: &BagGraph,
+    available: &HashMap<BagId, BigUint>,
+) -> Result<Vec<BagId>, GraphError> {
+    let none = BigUint::default();
+    let order = graph.topological_order()?;
+    let mut bags = Vec::new();
+    for outer in graph.ids() {
+        let needed = contents(graph, outer, &order);
+        let fits = needed
+            .iter()
+            .enumerate()
+            .all(|(bag, count)| count <= available.get(&bag).unwrap_or(&none));
+        if fits {
+            bags.push(outer);
+        }
+    }
+    bags.sort_by_key(|bag| graph.name(*bag));
+    Ok(bags)
+}
+
+/// The fewest and most bags `target` can be nested inside, counting from a bag that
+/// nothing else holds. A bag nothing holds is at depth 0. Only cycles among the bags
+/// holding `target` are errors.
+pub fn nesting_depth(graph: &BagGraph, target: BagId) -> Result<(usize, usize), GraphError> {
+    let mut depths = vec![(0, 0); graph.len()];
+    // Every bag holding one of these is in the order too, and comes before it
+    for bag in graph.topological_order_to(target)? {
+        let holders = graph.contained_by(bag);
+        if let Some(shallowest) = holders.iter().map(|e| depths[e.bag].0).min() {
+            let deepest = holders.iter().map(|e| depths[e.bag].1).max().unwrap();
+            depths[bag] = (shallowest + 1, deepest + 1);
+        }
+    }
+    Ok(depths[target])
+}
+
+#[test]
+fn test_totals() {
+    let graph = super::test_graph();
+    let totals = totals(&graph).unwrap();
+    let total = |name| totals[graph.id(name).unwrap()].to_u32_digits()[0];
+    assert_eq!(total("faded blue"), 1);
+    assert_eq!(total("dark olive"), 8);
+    assert_eq!(total("shiny gold"), 33);
+    assert_eq!(total("light red"), 187);
+    assert_eq!(total("dark orange"), 407);
+}
+
+#[test]
+fn test_huge_totals() {
+    // u32::MAX bags of u32::MAX bags, 40 levels deep, overflows any machine integer
+    let mut graph = BagGraph::default();
+    let ids: Vec<BagId> = (0..=40).map(|i| graph.intern(&i.to_string())).collect();
+    for pair in ids.windows(2) {
+        graph.add_rule(pair[0], u32::MAX, pair[1]);
+    }
+    let count = BigUint::from(u32::MAX);
+    let expected = (0..=40u32).fold(BigUint::default(), |sum, i| sum + count.pow(i));
+    assert_eq!(totals(&graph).unwrap()[0], expected);
+    let order = graph.topological_order_from(0).unwrap();
+    assert_eq!(contents(&graph, 0, &order)[40], count.pow(40));
+}
+
+#[test]
+fn test_within_budget() {
+    let graph = super::test_graph();
+    let names = |budget: u32| -> Vec<(&str, u32)> {
+        within_budget(&graph, &BigUint::from(budget))
+            .unwrap()
+            .into_iter()
+            .map(|(bag, total)| (graph.name(bag), total.to_u32_digits()[0]))
+            .collect()
+    };
+    assert_eq!(names(0), vec![]);
+    assert_eq!(
+        names(12),
+        vec![
+            ("dotted black", 1),
+            ("faded blue", 1),
+            ("dark olive", 8),
+            ("vibrant plum", 12)
+        ]
+    );
+    assert_eq!(names(1000).len(), 9);
+}
+
+#[test]
+fn test_packable() {
+    let graph = super::test_graph();
+    let id = |name: &str| graph.id(name).unwrap();
+    let packable = |available: &[(&str, u32)]| -> Vec<&str> {
+        let available = available
+            .iter()
+            .map(|(name, count)| (id(name), BigUint::from(*count)))
+            .collect();
+        packable(&graph, &available)
+            .unwrap()
+            .into_iter()
+            .map(|bag| graph.name(bag))
+            .collect()
+    };
+    assert_eq!(packable(&[]), vec!["dotted black", "faded blue"]);
+    assert_eq!(
+        packable(&[("faded blue", 5), ("dotted black", 6)]),
+        vec!["dark olive", "dotted black", "faded blue", "vibrant plum"]
+    );
+    // Shiny gold needs 13 faded blue and 16 dotted black, and bright white a
+    // shiny gold on top
+    let inner = [("dark olive", 1), ("vibrant plum", 2), ("dotted black", 16)];
+    assert_eq!(
+        packable(&[inner[0], inner[1], inner[2], ("faded blue", 13)]),
+        vec![
+            "dark olive",
+            "dotted black",
+            "faded blue",
+            "shiny gold",
+            "vibrant plum"
+        ]
+    );
+    assert!(
+        packable(&[inner[0], inner[1], inner[2], ("faded blue", 12)])
+            .iter()
+            .all(|name| *name != "shiny gold")
+    );
+    assert!(packable(&[
+        inner[0],
+        inner[1],
+        inner[2],
+        ("faded blue", 13),
+        ("shiny gold", 1)
+    ])
+    .contains(&"bright white"));
+}
+
+#[test]
+fn test_nesting_depth() {
+    let graph = super::test_graph();
+    let depth = |name| nesting_depth(&graph, graph.id(name).unwrap()).unwrap();
+    assert_eq!(depth("light red"), (0, 0));
+    assert_eq!(depth("dark orange"), (0, 0));
+    assert_eq!(depth("shiny gold"), (2, 2));
+    assert_eq!(depth("faded blue"), (2, 4));
+}
+
+#[test]
+fn test_unrelated_cycle() {
+    let mut graph = super::test_graph();
+    let gold = graph.id("shiny gold").unwrap();
+    let faded = graph.id("faded blue").unwrap();
+    let (blue, green) = (graph.intern("pale blue"), graph.intern("pale green"));
+    graph.add_rule(blue, 1, green);
+    graph.add_rule(green, 1, blue);
+    assert_eq!(total(&graph, gold).unwrap(), BigUint::from(33u32));
+    assert!(total(&graph, blue).is_err());
+    assert_eq!(nesting_depth(&graph, gold).unwrap(), (2, 2));
+    assert!(nesting_depth(&graph, green).is_err());
+
+    // A cycle around a bag, but not inside it
+    graph.add_rule(green, 1, gold);
+    assert_eq!(total(&graph, gold).unwrap(), BigUint::from(33u32));
+    assert!(nesting_depth(&graph, gold).is_err());
+    assert!(nesting_depth(&graph, faded).is_err());
+}
@@ -0,0 +1,341 @@
+//! Questions about a rule set, one per line:
+//!
+//! ```text
+//! containers-of <bag>
+//! contents-of <bag>
+//! path <bag> -> <bag>
+//! path <bag> <bag>
+//! depth <bag>
+//! roots
+//! leaves
+//! ```
+//!
+//! Without the arrow, `path` expects two colours of two words each, like the puzzle's.
+
+use std::{io::Write, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+
+use super::{
+    bag,
+    graph::{BagGraph, BagId},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Every bag that can hold the bag at any depth
+    ContainersOf(String),
+    /// The tree of bags inside the bag, with counts
+    ContentsOf(String),
+    /// Every way the first bag holds the second, with how many it holds that way
+    Path(String, String),
+    /// The most levels of bags nested inside the bag
+    Depth(String),
+    /// Bags no other bag can hold
+    Roots,
+    /// Bags that hold nothing
+    Leaves,
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, rest) = match s.split_once(' ') {
+            Some((command, rest)) => (command, rest.trim()),
+            None => (s, ""),
+        };
+        let bag = || {
+            if rest.is_empty() {
+                Err(anyhow!("{} needs a bag", command))
+            } else {
+                Ok(rest.to_string())
+            }
+        };
+        match command {
+            "containers-of" => Ok(Query::ContainersOf(bag()?)),
+            "contents-of" => Ok(Query::ContentsOf(bag()?)),
+            "depth" => Ok(Query::Depth(bag()?)),
+            "path" => {
+                let (from, to) = match rest.split_once("->") {
+                    Some((from, to)) => (from.trim().to_string(), to.trim().to_string()),
+                    None => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                        [a, b, c, d] => (format!("{} {}", a, b), format!("{} {}", c, d)),
+                        _ => bail!("expected path <bag> -> <bag>"),
+                    },
+                };
+                if from.is_empty() || to.is_empty() {
+                    bail!("expected path <bag> -> <bag>");
+                }
+                Ok(Query::Path(from, to))
+            }
+            "roots" | "leaves" if !rest.is_empty() => Err(anyhow!("{} takes no bag", command)),
+            "roots" => Ok(Query::Roots),
+            "leaves" => Ok(Query::Leaves),
+            _ => Err(anyhow!("unknown query `{}`", command)),
+        }
+    }
+}
+
+fn sorted_names(graph: &BagGraph, bags: impl Iterator<Item = BagId>) -> Vec<&str> {
+    let mut names: Vec<&str> = bags.map(|b| graph.name(b)).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Every path from `from` down to `to`, as the edges taken. Only bags that hold `to`
+/// are gone into, so shared contents that can't lead there are skipped.
+fn paths(graph: &BagGraph, from: BagId, to: BagId) -> Vec<Vec<(u32, BagId)>> {
+    let holds_to = graph.ancestors(to);
+    let mut paths = Vec::new();
+    // The current path, with the index of the next edge to try at each step
+    let mut path: Vec<(u32, BagId)> = Vec::new();
+    let mut stack: Vec<(BagId, usize)> = vec![(from, 0)];
+    while let Some((bag, edge)) = stack.last_mut() {
+        match graph.contains(*bag).get(*edge) {
+            Some(next) if next.bag != to && !holds_to[next.bag] => *edge += 1,
+            Some(next) => {
+                *edge += 1;
+                path.push((next.count, next.bag));
+                if next.bag == to {
+                    paths.push(path.clone());
+                    path.pop();
+                } else {
+                    stack.push((next.bag, 0));
+                }
+            }
+            None => {
+                stack.pop();
+                path.pop();
+            }
+        }
+    }
+    paths
+}
+
+pub fn run<W: Write>(graph: &BagGraph, query: &Query, out: &mut W) -> Result<()> {
+    match query {
+        Query::ContainersOf(name) => {
+            let id = bag(graph, name)?;
+            let containers = graph.can_contain_all(id)?;
+            let names = sorted_names(graph, graph.ids().filter(|b| containers[*b]));
+            writeln!(out, "{} bags can hold {}", names.len(), name)?;
+            for name in names {
+                writeln!(out, "  {}", name)?;
+            }
+        }
+        Query::ContentsOf(name) => {
+            let id = bag(graph, name)?;
+            let counts = graph.count_contained(id)?;
+            writeln!(out, "{} ({} inside)", name, counts[id])?;
+            // The graph has no cycles, so the tree is finite
+            let mut stack: Vec<(usize, u32, BagId)> = graph
+                .contains(id)
+                .iter()
+                .rev()
+                .map(|e| (1, e.count, e.bag))
+                .collect();
+            while let Some((level, count, bag)) = stack.pop() {
+                write!(
+                    out,
+                    "{:indent$}{} {}",
+                    "",
+                    count,
+                    graph.name(bag),
+                    indent = level * 2
+                )?;
+                match counts[bag] {
+                    0 => writeln!(out)?,
+                    inside => writeln!(out, " ({} inside each)", inside)?,
+                }
+                stack.extend(
+                    graph
+                        .contains(bag)
+                        .iter()
+                        .rev()
+                        .map(|e| (level + 1, e.count, e.bag)),
+                );
+            }
+        }
+        Query::Path(from_name, to_name) => {
+            let (from, to) = (bag(graph, from_name)?, bag(graph, to_name)?);
+            // Rejects cycles inside `from`, which would make the paths endless
+            graph.topological_order_from(from)?;
+            let paths = paths(graph, from, to);
+            let mut total: u64 = 0;
+            for path in &paths {
+                write!(out, "{}", from_name)?;
+                let mut bags: u64 = 1;
+                for (count, bag) in path {
+                    write!(out, " -{}-> {}", count, graph.name(*bag))?;
+                    bags = bags
+                        .checked_mul(*count as u64)
+                        .ok_or_else(|| anyhow!("too many {} bags", to_name))?;
+                }
+                writeln!(out, " ({} bags)", bags)?;
+                total = total
+                    .checked_add(bags)
+                    .ok_or_else(|| anyhow!("too many {} bags", to_name))?;
+            }
+            writeln!(
+                out,
+                "{} paths, {} {} bags in total",
+                paths.len(),
+                total,
+                to_name
+            )?;
+        }
+        Query::Depth(name) => {
+            let depth = graph.depth(bag(graph, name)?)?;
+            writeln!(out, "{} levels of bags inside {}", depth, name)?;
+        }
+        Query::Roots => {
+            let names = sorted_names(
+                graph,
+                graph.ids().filter(|b| graph.contained_by(*b).is_empty()),
+            );
+            writeln!(out, "{} bags aren't held by any other", names.len())?;
+            for name in names {
+                writeln!(out, "  {}", name)?;
+            }
+        }
+        Query::Leaves => {
+            let names = sorted_names(graph, graph.ids().filter(|b| graph.contains(*b).is_empty()));
+            writeln!(out, "{} bags hold nothing", names.len())?;
+            for name in names {
+                writeln!(out, "  {}", name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn query(s: &str) -> String {
+    let mut out = Vec::new();
+    run(&super::test_graph(), &s.parse().unwrap(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(
+        "containers-of shiny gold".parse::<Query>().unwrap(),
+        Query::ContainersOf("shiny gold".to_string())
+    );
+    assert_eq!(
+        " path light red  ->  shiny gold ".parse::<Query>().unwrap(),
+        Query::Path("light red".to_string(), "shiny gold".to_string())
+    );
+    assert_eq!("roots".parse::<Query>().unwrap(), Query::Roots);
+    assert!("depth".parse::<Query>().is_err());
+    assert_eq!(
+        "path light red shiny gold".parse::<Query>().unwrap(),
+        Query::Path("light red".to_string(), "shiny gold".to_string())
+    );
+    assert!("path light red".parse::<Query>().is_err());
+    assert!("path light red ->".parse::<Query>().is_err());
+    assert!("leaves light red".parse::<Query>().is_err());
+    assert!("parents-of shiny gold".parse::<Query>().is_err());
+}
+
+#[test]
+fn test_containers_and_contents() {
+    assert_eq!(
+        query("containers-of shiny gold"),
+        "4 bags can hold shiny gold\n  bright white\n  dark orange\n  light red\n  muted yellow\n"
+    );
+    assert_eq!(
+        query("contents-of shiny gold"),
+        "shiny gold (32 inside)
+  1 dark olive (7 inside each)
+    3 faded blue
+    4 dotted black
+  2 vibrant plum (11 inside each)
+    5 faded blue
+    6 dotted black
+"
+    );
+}
+
+#[test]
+fn test_path() {
+    assert_eq!(
+        query("path light red -> shiny gold"),
+        "light red -1-> bright white -1-> shiny gold (1 bags)
+light red -2-> muted yellow -2-> shiny gold (4 bags)
+2 paths, 5 shiny gold bags in total
+"
+    );
+    assert_eq!(
+        query("path shiny gold -> light red"),
+        "0 paths, 0 light red bags in total\n"
+    );
+
+    // A cycle elsewhere in the rules doesn't get in the way
+    let mut graph = super::test_graph();
+    let (blue, green) = (graph.intern("pale blue"), graph.intern("pale green"));
+    graph.add_rule(blue, 1, green);
+    graph.add_rule(green, 1, blue);
+    let mut out = Vec::new();
+    run(
+        &graph,
+        &"path shiny gold -> faded blue".parse().unwrap(),
+        &mut out,
+    )
+    .unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .ends_with("2 paths, 13 faded blue bags in total\n"));
+    let mut out = Vec::new();
+    assert!(run(
+        &graph,
+        &"path pale blue -> shiny gold".parse().unwrap(),
+        &mut out
+    )
+    .is_err());
+}
+
+#[test]
+fn test_path_skips_dead_ends() {
+    // Each level holds the next twice over, so there are 2^40 ways down to the
+    // bottom, none of which pass through the bag looked for
+    let mut graph = BagGraph::default();
+    let ids: Vec<BagId> = (0..=40)
+        .map(|i| graph.intern(&format!("level {}", i)))
+        .collect();
+    for pair in ids.windows(2) {
+        graph.add_rule(pair[0], 1, pair[1]);
+        graph.add_rule(pair[0], 1, pair[1]);
+    }
+    let target = graph.intern("shiny gold");
+    graph.add_rule(ids[0], 3, target);
+    let mut out = Vec::new();
+    run(
+        &graph,
+        &"path level 0 -> shiny gold".parse().unwrap(),
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "level 0 -3-> shiny gold (3 bags)\n1 paths, 3 shiny gold bags in total\n"
+    );
+}
+
+#[test]
+fn test_depth_roots_leaves() {
+    assert_eq!(
+        query("depth light red"),
+        "4 levels of bags inside light red\n"
+    );
+    assert_eq!(
+        query("roots"),
+        "2 bags aren't held by any other\n  dark orange\n  light red\n"
+    );
+    assert_eq!(
+        query("leaves"),
+        "2 bags hold nothing\n  dotted black\n  faded blue\n"
+    );
+}
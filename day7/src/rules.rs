@@ -0,0 +1,327 @@
+//! The puzzle's rule sentences, one per line:
+//!
+//! ```text
+//! rule     := colour bags "contain" contents "."
+//! contents := "no" "other" "bags" | item ("," item)*
+//! item     := count colour ("bag" | "bags")
+//! bags     := "bag" | "bags"
+//! ```
+//!
+//! A colour is any number of words. Words are separated by whitespace, and blank
+//! lines are skipped.
+
+use std::{collections::HashSet, fmt, ops::Deref};
+
+use super::graph::BagGraph;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// What was expected, and the word found instead if the line didn't end
+    Expected(&'static str, Option<String>),
+    InvalidCount(String),
+    /// A second rule for a colour
+    DuplicateRule(String),
+    /// A colour held by some bag that has no rule of its own
+    UndefinedColour(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    pub line: usize,
+    pub col: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.col)?;
+        match &self.problem {
+            Problem::Expected(what, Some(found)) => {
+                write!(f, "expected {}, found `{}`", what, found)
+            }
+            Problem::Expected(what, None) => write!(f, "expected {}, found end of line", what),
+            Problem::InvalidCount(count) => write!(f, "invalid bag count `{}`", count),
+            Problem::DuplicateRule(colour) => write!(f, "second rule for {} bags", colour),
+            Problem::UndefinedColour(colour) => write!(f, "no rule for {} bags", colour),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Comma,
+    Period,
+}
+
+impl Token<'_> {
+    fn text(&self) -> &str {
+        match self {
+            Token::Word(word) => word,
+            Token::Comma => ",",
+            Token::Period => ".",
+        }
+    }
+}
+
+/// Splits a line into words, commas and periods, each with its column
+fn tokenize(line: &str) -> Vec<(usize, Token<'_>)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+        if c.is_whitespace() || c == ',' || c == '.' {
+            if let Some(start) = start.take() {
+                tokens.push((start, Token::Word(&line[start..i])));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+        match c {
+            ',' => tokens.push((i, Token::Comma)),
+            '.' => tokens.push((i, Token::Period)),
+            _ => {}
+        }
+    }
+    tokens
+        .into_iter()
+        .map(|(i, token)| (line[..i].chars().count() + 1, token))
+        .collect()
+}
+
+struct Parser<'a> {
+    line: usize,
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+    /// Column just past the end of the line
+    end: usize,
+}
+
+/// A colour with the column it starts at
+type Colour = (usize, String);
+
+impl<'a> Parser<'a> {
+    fn col(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(col, _)| *col)
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.next).map(|(_, token)| *token)
+    }
+
+    fn error<T>(&self, col: usize, problem: Problem) -> Result<T, RuleError> {
+        Err(RuleError {
+            line: self.line,
+            col,
+            problem,
+        })
+    }
+
+    fn expected<T>(&self, what: &'static str) -> Result<T, RuleError> {
+        let found = self.peek().map(|token| token.text().to_string());
+        self.error(self.col(), Problem::Expected(what, found))
+    }
+
+    fn expect(&mut self, token: Token<'_>, what: &'static str) -> Result<(), RuleError> {
+        if self.peek() == Some(token) {
+            self.next += 1;
+            Ok(())
+        } else {
+            self.expected(what)
+        }
+    }
+
+    /// The words of a colour, up to and including "bag" or "bags"
+    fn colour(&mut self) -> Result<Colour, RuleError> {
+        let col = self.col();
+        let mut words = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Word("bag")) | Some(Token::Word("bags")) if !words.is_empty() => {
+                    self.next += 1;
+                    return Ok((col, words.join(" ")));
+                }
+                Some(Token::Word(word)) if word != "bag" && word != "bags" => {
+                    words.push(word);
+                    self.next += 1;
+                }
+                _ if words.is_empty() => return self.expected("a colour"),
+                _ => return self.expected("`bags`"),
+            }
+        }
+    }
+
+    fn count(&mut self) -> Result<u32, RuleError> {
+        let col = self.col();
+        match self.peek() {
+            Some(Token::Word(word)) if word.starts_with(|c: char| c.is_ascii_digit()) => {
+                self.next += 1;
+                match word.parse() {
+                    Ok(0) | Err(_) => self.error(col, Problem::InvalidCount(word.to_string())),
+                    Ok(count) => Ok(count),
+                }
+            }
+            _ => self.expected("a number of bags"),
+        }
+    }
+
+    /// The outer colour and the colours inside it with their counts
+    fn rule(&mut self) -> Result<(Colour, Vec<(u32, Colour)>), RuleError> {
+        let outer = self.colour()?;
+        self.expect(Token::Word("contain"), "`contain`")?;
+        let mut contents = Vec::new();
+        if self.peek() == Some(Token::Word("no")) {
+            self.next += 1;
+            self.expect(Token::Word("other"), "`other`")?;
+            self.expect(Token::Word("bags"), "`bags`")?;
+        } else {
+            loop {
+                let count = self.count()?;
+                contents.push((count, self.colour()?));
+                if self.peek() != Some(Token::Comma) {
+                    break;
+                }
+                self.next += 1;
+            }
+        }
+        self.expect(Token::Period, "`,` or `.`")?;
+        match self.peek() {
+            Some(_) => self.expected("the end of the line"),
+            None => Ok((outer, contents)),
+        }
+    }
+}
+
+/// Parses every rule, then checks that each colour has exactly one. Errors point at
+/// the first problem found.
+pub fn parse_bag_rules<T: Deref<Target = str>>(input: &[T]) -> Result<BagGraph, RuleError> {
+    let mut graph = BagGraph::default();
+    // Colours with a rule, and where each colour is held
+    let mut defined = HashSet::new();
+    let mut referenced = Vec::new();
+    for (i, line) in input.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parser = Parser {
+            line: i + 1,
+            tokens: tokenize(line),
+            next: 0,
+            end: line.chars().count() + 1,
+        };
+        let ((col, outer), contents) = parser.rule()?;
+        let id = graph.intern(&outer);
+        if !defined.insert(id) {
+            return parser.error(col, Problem::DuplicateRule(outer));
+        }
+        for (count, (col, inner)) in contents {
+            let inner = graph.intern(&inner);
+            referenced.push((inner, i + 1, col));
+            graph.add_rule(id, count, inner);
+        }
+    }
+    match referenced
+        .into_iter()
+        .find(|(id, ..)| !defined.contains(id))
+    {
+        Some((id, line, col)) => Err(RuleError {
+            line,
+            col,
+            problem: Problem::UndefinedColour(graph.name(id).to_string()),
+        }),
+        None => Ok(graph),
+    }
+}
+
+#[cfg(test)]
+fn parse_err(input: &str) -> RuleError {
+    let lines: Vec<&str> = input.lines().collect();
+    parse_bag_rules(&lines).unwrap_err()
+}
+
+#[test]
+fn test_multi_word_colours() {
+    let lines = [
+        "pale  light sky blue bags contain 1 red bag, 12 very dark green bags.",
+        "",
+        "red bags contain no other bags.",
+        "very dark green bags contain 3 red bags.",
+    ];
+    let graph = parse_bag_rules(&lines).unwrap();
+    let id = |name| graph.id(name).unwrap();
+    assert_eq!(graph.len(), 3);
+    let contains: Vec<(u32, &str)> = graph
+        .contains(id("pale light sky blue"))
+        .iter()
+        .map(|e| (e.count, graph.name(e.bag)))
+        .collect();
+    assert_eq!(contains, vec![(1, "red"), (12, "very dark green")]);
+    assert_eq!(graph.contains(id("very dark green"))[0].count, 3);
+}
+
+#[test]
+fn test_syntax_errors() {
+    let err = |line: &str| {
+        let err = parse_err(line);
+        (err.col, err.problem)
+    };
+    let expected = |what, found: Option<&str>| Problem::Expected(what, found.map(String::from));
+    assert_eq!(
+        err("bags contain no other bags."),
+        (1, expected("a colour", Some("bags")))
+    );
+    assert_eq!(
+        err("red bags hold no other bags."),
+        (10, expected("`contain`", Some("hold")))
+    );
+    assert_eq!(
+        err("red bags contain no bags."),
+        (21, expected("`other`", Some("bags")))
+    );
+    assert_eq!(
+        err("red bags contain 2 blue bags"),
+        (29, expected("`,` or `.`", None))
+    );
+    assert_eq!(
+        err("red bags contain 2 blue bags, ."),
+        (31, expected("a number of bags", Some(".")))
+    );
+    assert_eq!(
+        err("red bags contain 2 blue."),
+        (24, expected("`bags`", Some(".")))
+    );
+    assert_eq!(
+        err("red bags contain two blue bags."),
+        (18, expected("a number of bags", Some("two")))
+    );
+    assert_eq!(
+        err("red bags contain 0 blue bags."),
+        (18, Problem::InvalidCount("0".to_string()))
+    );
+    assert_eq!(
+        err("red bags contain 99999999999 blue bags."),
+        (18, Problem::InvalidCount("99999999999".to_string()))
+    );
+    assert_eq!(
+        err("red bags contain no other bags. blue"),
+        (33, expected("the end of the line", Some("blue")))
+    );
+}
+
+#[test]
+fn test_undefined_and_duplicate() {
+    let err = parse_err(
+        "red bags contain 1 blue bag.\nblue bags contain no other bags.\nred bags contain no other bags.",
+    );
+    assert_eq!((err.line, err.col), (3, 1));
+    assert_eq!(err.problem, Problem::DuplicateRule("red".to_string()));
+
+    let err = parse_err(
+        "red bags contain 1 blue bag, 2 dark green bags.\nblue bags contain no other bags.",
+    );
+    assert_eq!(
+        err.to_string(),
+        "line 1, column 32: no rule for dark green bags"
+    );
+}
@@ -0,0 +1,138 @@
+//! What can be found out about a program from its control-flow graph
+
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use super::{
+    flow::{self, Block},
+    instruction::Instruction,
+    repair::{self, Repair},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub program: Vec<Instruction>,
+    pub blocks: Vec<Block>,
+    /// Instructions with no path to them from the start
+    pub unreachable: Vec<usize>,
+    /// Loops with no way out, see `flow::closed_loops`
+    pub closed_loops: Vec<Vec<usize>>,
+    /// Flips of instructions the program runs after which it terminates
+    pub flips: Vec<Repair>,
+}
+
+impl Analysis {
+    pub fn new(program: &[Instruction]) -> Analysis {
+        let reachable = flow::reachable(program);
+        Analysis {
+            program: program.to_vec(),
+            blocks: flow::blocks(program),
+            unreachable: (0..program.len()).filter(|&pc| !reachable[pc]).collect(),
+            closed_loops: flow::closed_loops(program),
+            flips: repair::flips(program),
+        }
+    }
+
+    /// The control-flow graph in Graphviz's DOT language, one box per block.
+    /// Unreachable blocks are dashed and blocks in loops with no way out are red.
+    pub fn dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph program {{")?;
+        writeln!(out, "    node [shape=box, fontname=monospace];")?;
+        writeln!(out, "    end [shape=doublecircle, label=\"end\"];")?;
+        let len = self.program.len();
+        let name = |start: usize| {
+            if start == len {
+                "end".to_string()
+            } else {
+                format!("b{}", start)
+            }
+        };
+        for block in &self.blocks {
+            write!(out, "    {} [label=\"", name(block.start))?;
+            for pc in block.start..block.end {
+                write!(out, "{}: {}\\l", pc, self.program[pc])?;
+            }
+            write!(out, "\"")?;
+            if self.unreachable.contains(&block.start) {
+                write!(out, ", style=dashed")?;
+            }
+            if self.closed_loops.iter().any(|l| l.contains(&block.start)) {
+                write!(out, ", color=red")?;
+            }
+            writeln!(out, "];")?;
+        }
+        for block in &self.blocks {
+            for &next in &block.next {
+                writeln!(out, "    {} -> {};", name(block.start), name(next))?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, pcs: &[usize]) -> fmt::Result {
+    for (i, pc) in pcs.iter().enumerate() {
+        let sep = if i == 0 { "" } else { ", " };
+        write!(f, "{}{}", sep, pc)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions in {} blocks",
+            self.program.len(),
+            self.blocks.len()
+        )?;
+        if !self.unreachable.is_empty() {
+            write!(f, "unreachable: ")?;
+            write_list(f, &self.unreachable)?;
+            writeln!(f)?;
+        }
+        for pcs in &self.closed_loops {
+            write!(f, "loop with no way out: ")?;
+            write_list(f, pcs)?;
+            writeln!(f)?;
+        }
+        for repair in &self.flips {
+            writeln!(f, "terminates after flipping {}", repair)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_report() {
+    let analysis = Analysis::new(&super::test_program());
+    assert_eq!(analysis.unreachable, vec![5, 8]);
+    assert_eq!(analysis.flips.len(), 1);
+    assert_eq!(
+        analysis.to_string(),
+        "9 instructions in 6 blocks
+unreachable: 5, 8
+loop with no way out: 1, 2, 3, 4, 6, 7
+terminates after flipping 7: `jmp -4` -> `nop -4`, acc 8
+"
+    );
+
+    let analysis = Analysis::new(&[Instruction::Acc(1)]);
+    assert_eq!(analysis.to_string(), "1 instructions in 1 blocks\n");
+}
+
+#[test]
+fn test_dot() {
+    let mut out = Vec::new();
+    Analysis::new(&super::test_program()).dot(&mut out).unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    assert!(dot.starts_with("digraph program {\n"));
+    assert!(dot.contains("    b0 [label=\"0: nop +0\\l\"];\n"));
+    assert!(dot.contains("    b1 [label=\"1: acc +1\\l2: jmp +4\\l\", color=red];\n"));
+    assert!(dot.contains("    b5 [label=\"5: acc -99\\l\", style=dashed];\n"));
+    assert!(dot.contains("    b1 -> b6;\n"));
+    assert!(dot.contains("    b8 -> end;\n"));
+    assert!(dot.ends_with("}\n"));
+}
@@ -0,0 +1,497 @@
+//! Assembly source, one instruction per line. `#` starts a comment that runs to the
+//! end of the line, and lines with nothing else on them are skipped.
+//!
+//! A word ending in `:` labels the next instruction, on the same line or a later one,
+//! and jumps can name a label instead of an offset:
+//!
+//! ```text
+//! loop: add a -1
+//!       jnz a loop
+//! ```
+//!
+//! A label after the last instruction marks the end of the program.
+
+use std::{collections::HashMap, fmt, ops::Deref};
+
+use super::instruction::{Instruction, Operand, Reg};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    UnknownOpcode(String),
+    /// What the operand should have been
+    MissingOperand(&'static str),
+    InvalidOperand(&'static str, String),
+    ExtraOperand(String),
+    /// A jump to this instruction, outside the program
+    JumpOutOfRange(i64),
+    /// A label after the instruction on its line
+    MisplacedLabel(String),
+    InvalidLabel(String),
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub col: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.col)?;
+        match &self.problem {
+            Problem::UnknownOpcode(op) => write!(f, "unknown instruction `{}`", op),
+            Problem::MissingOperand(what) => write!(f, "expected {}", what),
+            Problem::InvalidOperand(what, found) => {
+                write!(f, "expected {}, found `{}`", what, found)
+            }
+            Problem::ExtraOperand(found) => write!(f, "unexpected `{}`", found),
+            Problem::JumpOutOfRange(target) => {
+                write!(f, "jump to instruction {}, outside the program", target)
+            }
+            Problem::MisplacedLabel(label) => {
+                write!(f, "label `{}` must come before the instruction", label)
+            }
+            Problem::InvalidLabel(label) => write!(f, "invalid label `{}`", label),
+            Problem::DuplicateLabel(label) => write!(f, "label `{}` is already defined", label),
+            Problem::UndefinedLabel(label) => write!(f, "no label `{}`", label),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// The words of a line before any comment, with their columns, and the column just
+/// past the last word
+fn words(line: &str) -> (Vec<(usize, &str)>, usize) {
+    let code = line.split('#').next().unwrap();
+    let mut words = Vec::new();
+    let mut start = None;
+    for (col, (i, c)) in code.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((col + 1, i)),
+            (true, Some((col, start_i))) => {
+                words.push((col, &code[start_i..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((col, start_i)) = start {
+        words.push((col, &code[start_i..]));
+    }
+    (words, code.trim_end().chars().count() + 1)
+}
+
+fn is_label(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Where a jump goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target<'a> {
+    Offset(i32),
+    Label(&'a str),
+}
+
+struct Operands<'a> {
+    words: std::vec::IntoIter<(usize, &'a str)>,
+    /// Column just past the last word
+    end: usize,
+    /// The jump target and its column, once it's been read
+    target: Option<(usize, Target<'a>)>,
+}
+
+type LineResult<T> = Result<T, (usize, Problem)>;
+
+impl<'a> Operands<'a> {
+    fn next<T>(
+        &mut self,
+        what: &'static str,
+        parse: impl Fn(&'a str) -> Option<T>,
+    ) -> LineResult<(usize, T)> {
+        match self.words.next() {
+            Some((col, word)) => match parse(word) {
+                Some(operand) => Ok((col, operand)),
+                None => Err((col, Problem::InvalidOperand(what, word.to_string()))),
+            },
+            None => Err((self.end, Problem::MissingOperand(what))),
+        }
+    }
+
+    fn num(&mut self) -> LineResult<i32> {
+        Ok(self.next("a number", |word| word.parse().ok())?.1)
+    }
+
+    fn reg(&mut self) -> LineResult<Reg> {
+        Ok(self.next("a register", |word| word.parse().ok())?.1)
+    }
+
+    fn value(&mut self) -> LineResult<Operand> {
+        Ok(self
+            .next("a register or a number", |word| word.parse().ok())?
+            .1)
+    }
+
+    /// A jump offset or label. Labels are resolved later, so this is 0 for now.
+    fn target(&mut self) -> LineResult<i32> {
+        let (col, target) = self.next("a number or a label", |word| match word.parse() {
+            Ok(offset) => Some(Target::Offset(offset)),
+            Err(_) if is_label(word) => Some(Target::Label(word)),
+            Err(_) => None,
+        })?;
+        self.target = Some((col, target));
+        match target {
+            Target::Offset(offset) => Ok(offset),
+            Target::Label(_) => Ok(0),
+        }
+    }
+
+    fn finish(&mut self) -> LineResult<()> {
+        match self.words.next() {
+            Some((col, word)) if word.ends_with(':') => {
+                Err((col, Problem::MisplacedLabel(word.to_string())))
+            }
+            Some((col, word)) => Err((col, Problem::ExtraOperand(word.to_string()))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Line<'a> {
+    /// Labels for the next instruction, with their columns
+    labels: Vec<(usize, &'a str)>,
+    instruction: Option<Instruction>,
+    /// Where the instruction jumps to, if it's a jump, and the column of the target
+    target: Option<(usize, Target<'a>)>,
+}
+
+/// Labels and the instruction on a line. Errors come with the column they're at.
+fn parse_line(line: &str) -> LineResult<Line<'_>> {
+    let (words, end) = words(line);
+    let mut words = words.into_iter().peekable();
+    let mut labels = Vec::new();
+    while let Some((col, label)) = words
+        .peek()
+        .and_then(|(col, w)| Some((*col, w.strip_suffix(':')?)))
+    {
+        if !is_label(label) {
+            return Err((col, Problem::InvalidLabel(label.to_string())));
+        }
+        labels.push((col, label));
+        words.next();
+    }
+    let (col, mnemonic) = match words.next() {
+        Some(word) => word,
+        None => {
+            return Ok(Line {
+                labels,
+                ..Line::default()
+            })
+        }
+    };
+    let mut ops = Operands {
+        words: words.collect::<Vec<_>>().into_iter(),
+        end,
+        target: None,
+    };
+    let instruction = match mnemonic {
+        "nop" => Instruction::Nop(ops.num()?),
+        "acc" => Instruction::Acc(ops.num()?),
+        "jmp" => Instruction::Jmp(ops.target()?),
+        "set" => Instruction::Set(ops.reg()?, ops.value()?),
+        "add" => Instruction::Add(ops.reg()?, ops.value()?),
+        "mul" => Instruction::Mul(ops.reg()?, ops.value()?),
+        "jz" => Instruction::Jz(ops.value()?, ops.target()?),
+        "jnz" => Instruction::Jnz(ops.value()?, ops.target()?),
+        "jgz" => Instruction::Jgz(ops.value()?, ops.target()?),
+        "out" => Instruction::Out(ops.value()?),
+        "hlt" => Instruction::Hlt,
+        _ => return Err((col, Problem::UnknownOpcode(mnemonic.to_string()))),
+    };
+    ops.finish()?;
+    Ok(Line {
+        labels,
+        instruction: Some(instruction),
+        target: ops.target,
+    })
+}
+
+/// A single instruction, without labels or symbolic jumps
+pub fn parse_instruction(s: &str) -> LineResult<Option<Instruction>> {
+    let line = parse_line(s)?;
+    match (line.labels.first(), line.target) {
+        (Some((col, label)), _) => Err((*col, Problem::MisplacedLabel(label.to_string()))),
+        (_, Some((col, Target::Label(label)))) => {
+            Err((col, Problem::UndefinedLabel(label.to_string())))
+        }
+        _ => Ok(line.instruction),
+    }
+}
+
+/// How far an instruction jumps, if it's a jump
+fn jump_offset(instruction: &mut Instruction) -> Option<&mut i32> {
+    match instruction {
+        Instruction::Jmp(offset)
+        | Instruction::Jz(_, offset)
+        | Instruction::Jnz(_, offset)
+        | Instruction::Jgz(_, offset) => Some(offset),
+        _ => None,
+    }
+}
+
+/// Parses a whole program, turning jumps to labels into offsets. Every jump must land
+/// on an instruction or just past the last one.
+pub fn parse_asm<T: Deref<Target = str>>(input: &[T]) -> Result<Vec<Instruction>, AsmError> {
+    let mut program = Vec::with_capacity(input.len());
+    let mut labels = HashMap::new();
+    // Each jump's target, with its line and column
+    let mut targets = Vec::new();
+    for (i, line) in input.iter().enumerate() {
+        let error = |(col, problem)| AsmError {
+            line: i + 1,
+            col,
+            problem,
+        };
+        let parsed = parse_line(line).map_err(error)?;
+        for (col, label) in parsed.labels {
+            if labels.insert(label, program.len()).is_some() {
+                return Err(error((col, Problem::DuplicateLabel(label.to_string()))));
+            }
+        }
+        if let Some(instruction) = parsed.instruction {
+            if let Some((col, target)) = parsed.target {
+                targets.push((program.len(), i + 1, col, target));
+            }
+            program.push(instruction);
+        }
+    }
+    let len = program.len();
+    for (pc, line, col, target) in targets {
+        let error = |problem| AsmError { line, col, problem };
+        let to = match target {
+            Target::Offset(offset) => pc as i64 + offset as i64,
+            Target::Label(label) => match labels.get(label) {
+                Some(to) => *to as i64,
+                None => return Err(error(Problem::UndefinedLabel(label.to_string()))),
+            },
+        };
+        if !(0..=len as i64).contains(&to) {
+            return Err(error(Problem::JumpOutOfRange(to)));
+        }
+        // Both ends are within the program, so the offset fits
+        *jump_offset(&mut program[pc]).unwrap() = (to - pc as i64) as i32;
+    }
+    Ok(program)
+}
+
+/// Writes a program back out as source, with a label on every instruction a jump
+/// lands on. Labels are named `l0`, `l1` and so on in program order.
+pub fn disassemble(program: &[Instruction]) -> String {
+    let mut targets = vec![false; program.len() + 1];
+    for (pc, instruction) in program.iter().enumerate() {
+        if let Some(offset) = jump_offset(&mut instruction.clone()) {
+            if let Some(target) = targets.get_mut((pc as i64 + *offset as i64) as usize) {
+                *target = true;
+            }
+        }
+    }
+    let mut names = vec![None; targets.len()];
+    for (n, pc) in (0..targets.len()).filter(|pc| targets[*pc]).enumerate() {
+        names[pc] = Some(format!("l{}", n));
+    }
+
+    let mut out = String::new();
+    for (pc, name) in names.iter().enumerate() {
+        if let Some(name) = name {
+            out += &format!("{}:\n", name);
+        }
+        let instruction = match program.get(pc) {
+            Some(instruction) => instruction,
+            None => break,
+        };
+        let mut offset = *instruction;
+        let target = jump_offset(&mut offset)
+            .and_then(|offset| names.get((pc as i64 + *offset as i64) as usize))
+            .and_then(|name| name.as_ref());
+        let line = match (instruction, target) {
+            (Instruction::Jmp(_), Some(name)) => format!("jmp {}", name),
+            (Instruction::Jz(x, _), Some(name)) => format!("jz {} {}", x, name),
+            (Instruction::Jnz(x, _), Some(name)) => format!("jnz {} {}", x, name),
+            (Instruction::Jgz(x, _), Some(name)) => format!("jgz {} {}", x, name),
+            _ => instruction.to_string(),
+        };
+        out += &format!("    {}\n", line);
+    }
+    out
+}
+
+#[cfg(test)]
+fn parse_err(source: &str) -> AsmError {
+    let lines: Vec<&str> = source.lines().collect();
+    parse_asm(&lines).unwrap_err()
+}
+
+#[test]
+fn test_comments_and_blank_lines() {
+    let lines = [
+        "# counts to three",
+        "",
+        "  set a +3   # a counts down",
+        "\tadd a -1",
+        "   ",
+        "jnz a -1#back up",
+    ];
+    assert_eq!(
+        parse_asm(&lines).unwrap(),
+        vec![
+            Instruction::Set(Reg::Named(0), Operand::Imm(3)),
+            Instruction::Add(Reg::Named(0), Operand::Imm(-1)),
+            Instruction::Jnz(Operand::Reg(Reg::Named(0)), -1),
+        ]
+    );
+}
+
+#[test]
+fn test_errors() {
+    let err = |source| {
+        let err = parse_err(source);
+        (err.line, err.col, err.problem)
+    };
+    let invalid = |what, found: &str| Problem::InvalidOperand(what, found.to_string());
+    assert_eq!(
+        err("nop +0\n  jpm +1"),
+        (2, 3, Problem::UnknownOpcode("jpm".to_string()))
+    );
+    assert_eq!(err("acc"), (1, 4, Problem::MissingOperand("a number")));
+    assert_eq!(
+        err("acc   # no operand"),
+        (1, 4, Problem::MissingOperand("a number"))
+    );
+    assert_eq!(err("acc x"), (1, 5, invalid("a number", "x")));
+    assert_eq!(
+        err("acc 99999999999"),
+        (1, 5, invalid("a number", "99999999999"))
+    );
+    assert_eq!(err("set 4 +1"), (1, 5, invalid("a register", "4")));
+    assert_eq!(
+        err("jz a"),
+        (1, 5, Problem::MissingOperand("a number or a label"))
+    );
+    assert_eq!(
+        err("out q r"),
+        (1, 7, Problem::ExtraOperand("r".to_string()))
+    );
+    assert_eq!(
+        err("hlt now"),
+        (1, 5, Problem::ExtraOperand("now".to_string()))
+    );
+    assert_eq!(
+        parse_err("mul a b c").to_string(),
+        "line 1, column 9: unexpected `c`"
+    );
+}
+
+#[test]
+fn test_jump_range() {
+    let lines = ["# jumps", "jmp +2", "", "jz a -1", "jmp -3  # too far"];
+    let err = parse_asm(&lines).unwrap_err();
+    assert_eq!((err.line, err.col), (5, 5));
+    assert_eq!(err.problem, Problem::JumpOutOfRange(-1));
+    assert_eq!(parse_err("jnz a +2").problem, Problem::JumpOutOfRange(2));
+    // Just past the end terminates, and a nop's operand isn't a jump
+    assert!(parse_asm(&["jmp +2", "nop -100", "jgz a -2"]).is_ok());
+}
+
+#[test]
+fn test_labels() {
+    let source = "
+start:
+    set a +3
+loop: add a -1   # counts down
+    jnz a loop
+    jz a end
+    jmp start
+end:";
+    let lines: Vec<&str> = source.lines().collect();
+    let program = parse_asm(&lines).unwrap();
+    assert_eq!(
+        program[2],
+        Instruction::Jnz(Operand::Reg(Reg::Named(0)), -1)
+    );
+    assert_eq!(program[3], Instruction::Jz(Operand::Reg(Reg::Named(0)), 2));
+    assert_eq!(program[4], Instruction::Jmp(-4));
+
+    let err = |source| {
+        let err = parse_err(source);
+        (err.line, err.col, err.problem)
+    };
+    assert_eq!(
+        err("a: nop +0\na: hlt"),
+        (2, 1, Problem::DuplicateLabel("a".to_string()))
+    );
+    assert_eq!(
+        err("jmp nowhere"),
+        (1, 5, Problem::UndefinedLabel("nowhere".to_string()))
+    );
+    assert_eq!(
+        err("jmp +1 x:"),
+        (1, 8, Problem::MisplacedLabel("x:".to_string()))
+    );
+    assert_eq!(
+        err("1x: hlt"),
+        (1, 1, Problem::InvalidLabel("1x".to_string()))
+    );
+    assert_eq!(
+        err("acc start\nstart: hlt"),
+        (
+            1,
+            5,
+            Problem::InvalidOperand("a number", "start".to_string())
+        )
+    );
+}
+
+#[test]
+fn test_disassemble() {
+    use Instruction::*;
+    let program = vec![
+        Nop(0),
+        Acc(1),
+        Jmp(4),
+        Acc(3),
+        Jmp(-3),
+        Jz(Operand::Reg(Reg::Acc), 2),
+        Out(Operand::Imm(-7)),
+        Jmp(-4),
+        Jgz(Operand::Imm(1), 1),
+    ];
+    let source = disassemble(&program);
+    assert_eq!(
+        source,
+        "    nop +0
+l0:
+    acc +1
+    jmp l2
+l1:
+    acc +3
+    jmp l0
+    jz acc l3
+l2:
+    out -7
+l3:
+    jmp l1
+    jgz +1 l4
+l4:
+"
+    );
+    let lines: Vec<&str> = source.lines().collect();
+    assert_eq!(parse_asm(&lines).unwrap(), program);
+}
@@ -0,0 +1,366 @@
+//! A step debugger reading commands from a line-based input:
+//!
+//! ```text
+//! step [n]              runs one instruction, or n
+//! continue              runs until a breakpoint, a change to acc if watched, or a halt
+//! break <pc|opcode>     stops before the instruction at pc, or before any of an opcode
+//! delete <pc|opcode>    removes a breakpoint
+//! watch                 turns stopping when acc changes on or off
+//! list [n]              shows n instructions either side of pc
+//! flip [pc]             swaps a nop and jmp, at pc or the current instruction
+//! patch <pc> <ins>      replaces the instruction at pc
+//! regs                  shows acc and any registers that aren't 0
+//! quit
+//! ```
+
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail};
+
+use super::{
+    instruction::Instruction,
+    machine::{Halt, Machine},
+};
+
+/// How many instructions `continue` runs before giving up
+const STEP_LIMIT: usize = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Pc(usize),
+    Opcode(String),
+}
+
+impl FromStr for Breakpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(pc) => Ok(Breakpoint::Pc(pc)),
+            Err(_) if s.chars().all(|c| c.is_ascii_lowercase()) => {
+                Ok(Breakpoint::Opcode(s.to_string()))
+            }
+            Err(_) => Err(anyhow!("expected an address or an opcode, got `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Break(Breakpoint),
+    Delete(Breakpoint),
+    Watch,
+    List(usize),
+    Flip(Option<usize>),
+    Patch(usize, Instruction),
+    Regs,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, rest) = match s.split_once(char::is_whitespace) {
+            Some((command, rest)) => (command, rest.trim()),
+            None => (s, ""),
+        };
+        let number = |default: Option<usize>| match (rest, default) {
+            ("", Some(default)) => Ok(default),
+            _ => rest
+                .parse()
+                .map_err(|_| anyhow!("expected a number, got `{}`", rest)),
+        };
+        let none = |command| {
+            if rest.is_empty() {
+                Ok(command)
+            } else {
+                Err(anyhow!("unexpected `{}`", rest))
+            }
+        };
+        match command {
+            "s" | "step" => Ok(Command::Step(number(Some(1))?)),
+            "c" | "continue" => none(Command::Continue),
+            "b" | "break" => Ok(Command::Break(rest.parse()?)),
+            "d" | "delete" => Ok(Command::Delete(rest.parse()?)),
+            "w" | "watch" => none(Command::Watch),
+            "l" | "list" => Ok(Command::List(number(Some(2))?)),
+            "f" | "flip" if rest.is_empty() => Ok(Command::Flip(None)),
+            "f" | "flip" => Ok(Command::Flip(Some(number(None)?))),
+            "p" | "patch" => match rest.split_once(char::is_whitespace) {
+                Some((pc, instruction)) => Ok(Command::Patch(
+                    pc.parse()
+                        .map_err(|_| anyhow!("expected an address, got `{}`", pc))?,
+                    instruction.parse()?,
+                )),
+                None => bail!("expected patch <pc> <instruction>"),
+            },
+            "r" | "regs" => none(Command::Regs),
+            "q" | "quit" => none(Command::Quit),
+            _ => bail!("unknown command `{}`", command),
+        }
+    }
+}
+
+pub struct Debugger {
+    pub machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    break_opcodes: BTreeSet<String>,
+    watch: bool,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine) -> Debugger {
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            break_opcodes: BTreeSet::new(),
+            watch: false,
+        }
+    }
+
+    /// The instructions up to `n` either side of pc, marking pc with `>` and
+    /// breakpoints with `*`
+    fn list<W: Write>(&self, n: usize, out: &mut W) -> io::Result<()> {
+        let pc = self.machine.pc;
+        let program = &self.machine.program;
+        let start = pc.saturating_sub(n);
+        for (i, instruction) in program
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(pc.saturating_add(n).saturating_add(1) - start)
+        {
+            let breakpoint = if self.breakpoints.contains(&i) {
+                '*'
+            } else {
+                ' '
+            };
+            let current = if i == pc { '>' } else { ' ' };
+            writeln!(out, "{}{} {:>4}  {}", breakpoint, current, i, instruction)?;
+        }
+        if pc >= program.len() {
+            writeln!(out, " > {:>4}  (end of program)", pc)?;
+        }
+        Ok(())
+    }
+
+    fn status<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let m = &self.machine;
+        writeln!(out, "pc {}, acc {}, steps {}", m.pc, m.acc, m.steps)?;
+        self.list(2, out)
+    }
+
+    fn continue_<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        // Leave the breakpoint we may be stopped on, watching that instruction too
+        let mut acc = self.machine.acc;
+        if let Err(halt) = self.machine.step() {
+            return writeln!(out, "{}", halt);
+        }
+        if self.watch && self.machine.acc != acc {
+            return writeln!(out, "acc {} -> {}", acc, self.machine.acc);
+        }
+        acc = self.machine.acc;
+        let mut changed = None;
+        let (breakpoints, opcodes, watch) = (&self.breakpoints, &self.break_opcodes, self.watch);
+        let halt = self.machine.run_until(STEP_LIMIT, |m| {
+            if watch && m.acc != acc {
+                changed = Some((acc, m.acc));
+            }
+            acc = m.acc;
+            changed.is_some()
+                || breakpoints.contains(&m.pc)
+                || m.program
+                    .get(m.pc)
+                    .is_some_and(|i| opcodes.contains(i.mnemonic()))
+        });
+        match (halt, changed) {
+            (Halt::Stopped, Some((from, to))) => writeln!(out, "acc {} -> {}", from, to),
+            (Halt::Stopped, None) => writeln!(out, "breakpoint at {}", self.machine.pc),
+            (halt, _) => writeln!(out, "{}", halt),
+        }
+    }
+
+    /// Runs one command. Returns false once it's time to quit.
+    pub fn command<W: Write>(&mut self, command: Command, out: &mut W) -> io::Result<bool> {
+        match command {
+            Command::Step(n) => {
+                for _ in 0..n {
+                    if let Err(halt) = self.machine.step() {
+                        writeln!(out, "{}", halt)?;
+                        break;
+                    }
+                }
+            }
+            Command::Continue => self.continue_(out)?,
+            Command::Break(Breakpoint::Pc(pc)) => {
+                self.breakpoints.insert(pc);
+            }
+            Command::Break(Breakpoint::Opcode(op)) => {
+                self.break_opcodes.insert(op);
+            }
+            Command::Delete(Breakpoint::Pc(pc)) => {
+                self.breakpoints.remove(&pc);
+            }
+            Command::Delete(Breakpoint::Opcode(op)) => {
+                self.break_opcodes.remove(&op);
+            }
+            Command::Watch => {
+                self.watch = !self.watch;
+                let state = if self.watch { "on" } else { "off" };
+                writeln!(out, "watching acc: {}", state)?;
+            }
+            Command::List(n) => return self.list(n, out).map(|_| true),
+            Command::Flip(pc) => {
+                let pc = pc.unwrap_or(self.machine.pc);
+                if !self.machine.flip(pc) {
+                    writeln!(out, "no nop or jmp at {}", pc)?;
+                }
+            }
+            Command::Patch(pc, instruction) => match self.machine.program.get_mut(pc) {
+                Some(old) => *old = instruction,
+                None => writeln!(out, "no instruction at {}", pc)?,
+            },
+            Command::Regs => {
+                write!(out, "acc {}", self.machine.acc)?;
+                for (name, val) in ('a'..='z').zip(&self.machine.regs) {
+                    if *val != 0 {
+                        write!(out, ", {} {}", name, val)?;
+                    }
+                }
+                return writeln!(out).map(|_| true);
+            }
+            Command::Quit => return Ok(false),
+        }
+        self.status(out)?;
+        Ok(true)
+    }
+
+    /// Reads commands until `quit` or the end of the input
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W) -> io::Result<()> {
+        self.status(out)?;
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse() {
+                Ok(command) => {
+                    if !self.command(command, out)? {
+                        break;
+                    }
+                }
+                Err(e) => writeln!(out, "error: {}", e)?,
+            }
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn debug(source: &str, commands: &str) -> String {
+    let lines: Vec<&str> = source.trim().lines().map(str::trim).collect();
+    let program = super::parse_asm(&lines).unwrap();
+    let mut out = Vec::new();
+    Debugger::new(Machine::new(program))
+        .run(commands.as_bytes(), &mut out)
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_commands() {
+    assert_eq!("s".parse::<Command>().unwrap(), Command::Step(1));
+    assert_eq!("step 5".parse::<Command>().unwrap(), Command::Step(5));
+    assert_eq!(
+        "b jmp".parse::<Command>().unwrap(),
+        Command::Break(Breakpoint::Opcode("jmp".to_string()))
+    );
+    assert_eq!(
+        "patch 3 acc -2".parse::<Command>().unwrap(),
+        Command::Patch(3, Instruction::Acc(-2))
+    );
+    assert_eq!("flip".parse::<Command>().unwrap(), Command::Flip(None));
+    assert!("step x".parse::<Command>().is_err());
+    assert!("continue 3".parse::<Command>().is_err());
+    assert!("break 1x".parse::<Command>().is_err());
+    assert!("patch 3 xyz".parse::<Command>().is_err());
+    assert!("go".parse::<Command>().is_err());
+}
+
+#[test]
+fn test_list_everything() {
+    // However far either side, the listing stops at the ends of the program
+    let out = debug(super::TEST_INPUT, &format!("list {}", usize::MAX));
+    assert!(out.ends_with(
+        " >    0  nop +0
+      1  acc +1
+      2  jmp +4
+      3  acc +3
+      4  jmp -3
+      5  acc -99
+      6  acc +1
+      7  jmp -4
+      8  acc +6
+"
+    ));
+}
+
+#[test]
+fn test_breakpoints() {
+    let out = debug(
+        super::TEST_INPUT,
+        "break 4\nb acc\nc\nc\nd acc\nc\nc\nd 4\nc\nquit\nstep",
+    );
+    let stops: Vec<&str> = out
+        .lines()
+        .filter(|l| !l.starts_with(' ') && !l.starts_with('*'))
+        .collect();
+    assert_eq!(
+        stops,
+        vec![
+            "pc 0, acc 0, steps 0",
+            "pc 0, acc 0, steps 0",
+            "pc 0, acc 0, steps 0",
+            "breakpoint at 1",
+            "pc 1, acc 0, steps 1",
+            "breakpoint at 6",
+            "pc 6, acc 1, steps 3",
+            "pc 6, acc 1, steps 3",
+            "breakpoint at 4",
+            "pc 4, acc 5, steps 6",
+            "breakpoint at 4",
+            "pc 4, acc 10, steps 12",
+            "pc 4, acc 10, steps 12",
+            "loops forever before 1, with acc 15",
+            "pc 1, acc 15, steps 19",
+        ]
+    );
+    assert!(out.contains("\n*>    4  jmp -3\n"));
+    assert!(out.ends_with(" >    1  acc +1\n      2  jmp +4\n      3  acc +3\n"));
+}
+
+#[test]
+fn test_watch_and_flip() {
+    let out = debug(super::TEST_INPUT, "watch\nc\nc\nflip 7\nw\nc");
+    assert!(out.contains("acc 0 -> 1\npc 2, acc 1, steps 2\n"));
+    assert!(out.contains("acc 1 -> 2\npc 7, acc 2, steps 4\n"));
+    assert!(out.contains("watching acc: off"));
+    assert!(out.contains("terminated\npc 9, acc 8, steps 6\n"));
+    assert!(out.ends_with(" >    9  (end of program)\n"));
+}
+
+#[test]
+fn test_watch_stopped_on_acc() {
+    let out = debug("acc +1\nacc +1\nnop +0\nnop +0", "s\nwatch\nc\nc");
+    assert!(out.contains("acc 1 -> 2\npc 2, acc 2, steps 2\n"));
+    assert!(out.contains("terminated\npc 4, acc 2, steps 4\n"));
+}
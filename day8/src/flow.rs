@@ -0,0 +1,251 @@
+//! The control-flow graph of a program. Node `i` is the instruction at `i`, and node
+//! `program.len()` stands for termination. Conditional jumps have an edge both ways,
+//! whatever their condition, and jumps out of the program have none.
+
+use super::instruction::Instruction;
+
+/// Where `instruction` at `pc` can go next
+pub fn successors(instruction: Instruction, pc: usize, len: usize) -> Vec<usize> {
+    let jump = |offset: i32| {
+        let target = pc as i64 + offset as i64;
+        (0..=len as i64)
+            .contains(&target)
+            .then_some(target as usize)
+    };
+    match instruction {
+        Instruction::Hlt => vec![len],
+        Instruction::Jmp(offset) => jump(offset).into_iter().collect(),
+        Instruction::Jz(_, offset) | Instruction::Jnz(_, offset) | Instruction::Jgz(_, offset) => {
+            let mut next = vec![pc + 1];
+            next.extend(jump(offset).filter(|&target| target != pc + 1));
+            next
+        }
+        _ => vec![pc + 1],
+    }
+}
+
+/// The edges into each node, termination included
+pub fn predecessors(program: &[Instruction]) -> Vec<Vec<usize>> {
+    let mut preds = vec![Vec::new(); program.len() + 1];
+    for (pc, instruction) in program.iter().enumerate() {
+        for next in successors(*instruction, pc, program.len()) {
+            preds[next].push(pc);
+        }
+    }
+    preds
+}
+
+/// Which nodes have a path to termination, found by walking the edges backwards from
+/// it
+pub fn reaches_end(program: &[Instruction]) -> Vec<bool> {
+    let preds = predecessors(program);
+    let mut reaches = vec![false; program.len() + 1];
+    reaches[program.len()] = true;
+    let mut todo = vec![program.len()];
+    while let Some(node) = todo.pop() {
+        for &pred in &preds[node] {
+            if !reaches[pred] {
+                reaches[pred] = true;
+                todo.push(pred);
+            }
+        }
+    }
+    reaches
+}
+
+/// Which nodes have a path from the first instruction
+pub fn reachable(program: &[Instruction]) -> Vec<bool> {
+    let mut seen = vec![false; program.len() + 1];
+    seen[0] = true;
+    let mut todo = vec![0];
+    while let Some(pc) = todo.pop() {
+        let next = match program.get(pc) {
+            Some(instruction) => successors(*instruction, pc, program.len()),
+            None => continue,
+        };
+        for next in next {
+            if !seen[next] {
+                seen[next] = true;
+                todo.push(next);
+            }
+        }
+    }
+    seen
+}
+
+/// A run of instructions only ever entered at the start and left at the end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// Just past the last instruction
+    pub end: usize,
+    /// The starts of the blocks run next, with `program.len()` for termination
+    pub next: Vec<usize>,
+}
+
+pub fn blocks(program: &[Instruction]) -> Vec<Block> {
+    let len = program.len();
+    let mut leader = vec![false; len + 1];
+    leader[0] = true;
+    for (pc, instruction) in program.iter().enumerate() {
+        let next = successors(*instruction, pc, len);
+        if next != [pc + 1] {
+            leader[pc + 1] = true;
+            for next in next {
+                leader[next] = true;
+            }
+        }
+    }
+    let starts: Vec<usize> = (0..len).filter(|&pc| leader[pc]).collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(len);
+            Block {
+                start,
+                end,
+                next: successors(program[end - 1], end - 1, len),
+            }
+        })
+        .collect()
+}
+
+/// Loops that nothing leads out of, so once the program enters one it never
+/// terminates. Each is the addresses of its instructions, in order.
+///
+/// These are the strongly connected components with no edges out, found with
+/// Kosaraju's algorithm: a depth-first search records when each node is finished, and
+/// a search of the reversed graph from the last finished node on picks out each
+/// component in turn.
+pub fn closed_loops(program: &[Instruction]) -> Vec<Vec<usize>> {
+    let len = program.len();
+    let succs: Vec<Vec<usize>> = program
+        .iter()
+        .enumerate()
+        .map(|(pc, instruction)| successors(*instruction, pc, len))
+        .chain(std::iter::once(Vec::new()))
+        .collect();
+    let preds = predecessors(program);
+
+    let mut finished = Vec::with_capacity(len + 1);
+    let mut seen = vec![false; len + 1];
+    for root in 0..=len {
+        if seen[root] {
+            continue;
+        }
+        seen[root] = true;
+        // Each node with how many of its successors have been looked at
+        let mut stack = vec![(root, 0)];
+        while let Some((node, i)) = stack.last_mut() {
+            match succs[*node].get(*i) {
+                Some(&next) => {
+                    *i += 1;
+                    if !seen[next] {
+                        seen[next] = true;
+                        stack.push((next, 0));
+                    }
+                }
+                None => {
+                    finished.push(*node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let mut component = vec![usize::MAX; len + 1];
+    let mut loops = Vec::new();
+    for (id, &root) in finished.iter().rev().enumerate() {
+        if component[root] != usize::MAX {
+            continue;
+        }
+        component[root] = id;
+        let mut members = vec![root];
+        let mut todo = vec![root];
+        while let Some(node) = todo.pop() {
+            for &pred in &preds[node] {
+                if component[pred] == usize::MAX {
+                    component[pred] = id;
+                    members.push(pred);
+                    todo.push(pred);
+                }
+            }
+        }
+        let is_loop = members.len() > 1 || succs[root].contains(&root);
+        let closed = members
+            .iter()
+            .all(|&node| succs[node].iter().all(|&next| component[next] == id));
+        if is_loop && closed {
+            members.sort_unstable();
+            loops.push(members);
+        }
+    }
+    loops.sort();
+    loops
+}
+
+#[test]
+fn test_successors() {
+    use super::instruction::Operand::Imm;
+    use Instruction::*;
+    assert_eq!(successors(Nop(-5), 2, 4), vec![3]);
+    assert_eq!(successors(Acc(1), 3, 4), vec![4]);
+    assert_eq!(successors(Jmp(-2), 2, 4), vec![0]);
+    assert_eq!(successors(Jmp(-3), 2, 4), vec![]);
+    assert_eq!(successors(Jmp(3), 2, 4), vec![]);
+    assert_eq!(successors(Hlt, 0, 4), vec![4]);
+    assert_eq!(successors(Jz(Imm(0), -1), 1, 4), vec![2, 0]);
+    assert_eq!(successors(Jnz(Imm(0), 1), 1, 4), vec![2]);
+}
+
+#[test]
+fn test_reaches_end() {
+    use Instruction::*;
+    // 0 -> 1 -> 4 -> end, and 2 <-> 3
+    let program = [Nop(0), Jmp(3), Jmp(1), Jmp(-1), Acc(1)];
+    assert_eq!(
+        reaches_end(&program),
+        vec![true, true, false, false, true, true]
+    );
+    assert_eq!(reaches_end(&[]), vec![true]);
+}
+
+#[test]
+fn test_blocks() {
+    let program = super::test_program();
+    let blocks: Vec<(usize, usize, Vec<usize>)> = blocks(&program)
+        .into_iter()
+        .map(|block| (block.start, block.end, block.next))
+        .collect();
+    assert_eq!(
+        blocks,
+        vec![
+            (0, 1, vec![1]),
+            (1, 3, vec![6]),
+            (3, 5, vec![1]),
+            (5, 6, vec![6]),
+            (6, 8, vec![3]),
+            (8, 9, vec![9]),
+        ]
+    );
+    assert_eq!(
+        reachable(&program),
+        vec![true, true, true, true, true, false, true, true, false, false]
+    );
+    assert_eq!(closed_loops(&program), vec![vec![1, 2, 3, 4, 6, 7]]);
+}
+
+#[test]
+fn test_closed_loops() {
+    use super::instruction::Operand::Reg;
+    use Instruction::*;
+    assert_eq!(closed_loops(&[Jmp(0), Acc(1)]), vec![vec![0]]);
+    // A conditional jump may leave the first loop, for the second
+    let acc = Reg(super::instruction::Reg::Acc);
+    assert_eq!(
+        closed_loops(&[Acc(1), Jnz(acc, -1), Jmp(1), Jmp(-1)]),
+        vec![vec![2, 3]]
+    );
+    assert_eq!(closed_loops(&[]), Vec::<Vec<usize>>::new());
+}
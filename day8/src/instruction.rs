@@ -0,0 +1,194 @@
+//! The handheld's instruction set. `nop`, `acc` and `jmp` are the puzzle's own; the
+//! rest work on registers `a` to `z` as well as `acc`, the accumulator.
+//!
+//! ```text
+//! nop n       does nothing
+//! acc n       adds n to acc
+//! jmp n       jumps n instructions
+//! set r x     r = x
+//! add r x     r = r + x
+//! mul r x     r = r * x
+//! jz x n      jumps n instructions if x is 0
+//! jnz x n     jumps n instructions if x isn't 0
+//! jgz x n     jumps n instructions if x is greater than 0
+//! out x       outputs x
+//! hlt         terminates the program
+//! ```
+//!
+//! `x` is a register or a number, `n` always a number. Arithmetic wraps around.
+
+use std::{fmt, str::FromStr};
+
+use super::asm::{self, AsmError, Problem};
+
+/// `a` to `z` are 0 to 25
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    Acc,
+    Named(u8),
+}
+
+impl FromStr for Reg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            b"acc" => Ok(Reg::Acc),
+            [c @ b'a'..=b'z'] => Ok(Reg::Named(c - b'a')),
+            _ => Err(anyhow::anyhow!("expected a register, got `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reg::Acc => write!(f, "acc"),
+            Reg::Named(i) => write!(f, "{}", (b'a' + i) as char),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Reg(Reg),
+    Imm(i32),
+}
+
+impl FromStr for Operand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(reg) => Ok(Operand::Reg(reg)),
+            Err(_) => s
+                .parse()
+                .map(Operand::Imm)
+                .map_err(|_| anyhow::anyhow!("expected a register or a number, got `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => write!(f, "{}", reg),
+            Operand::Imm(val) => write!(f, "{:+}", val),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop(i32),
+    Acc(i32),
+    Jmp(i32),
+    Set(Reg, Operand),
+    Add(Reg, Operand),
+    Mul(Reg, Operand),
+    Jz(Operand, i32),
+    Jnz(Operand, i32),
+    Jgz(Operand, i32),
+    Out(Operand),
+    Hlt,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Nop(_) => "nop",
+            Instruction::Acc(_) => "acc",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Set(..) => "set",
+            Instruction::Add(..) => "add",
+            Instruction::Mul(..) => "mul",
+            Instruction::Jz(..) => "jz",
+            Instruction::Jnz(..) => "jnz",
+            Instruction::Jgz(..) => "jgz",
+            Instruction::Out(_) => "out",
+            Instruction::Hlt => "hlt",
+        }
+    }
+}
+
+/// One instruction in the assembly syntax, with a number for any jump
+impl FromStr for Instruction {
+    type Err = AsmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |col, problem| AsmError {
+            line: 1,
+            col,
+            problem,
+        };
+        match asm::parse_instruction(s) {
+            Ok(Some(instruction)) => Ok(instruction),
+            Ok(None) => Err(error(1, Problem::MissingOperand("an instruction"))),
+            Err((col, problem)) => Err(error(col, problem)),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self {
+            Instruction::Nop(n) | Instruction::Acc(n) | Instruction::Jmp(n) => {
+                write!(f, " {:+}", n)
+            }
+            Instruction::Set(r, x) | Instruction::Add(r, x) | Instruction::Mul(r, x) => {
+                write!(f, " {} {}", r, x)
+            }
+            Instruction::Jz(x, n) | Instruction::Jnz(x, n) | Instruction::Jgz(x, n) => {
+                write!(f, " {} {:+}", x, n)
+            }
+            Instruction::Out(x) => write!(f, " {}", x),
+            Instruction::Hlt => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let source = [
+        "nop +0",
+        "acc -99",
+        "jmp +4",
+        "set a +5",
+        "add acc b",
+        "mul z -2",
+        "jz a -3",
+        "jnz acc +2",
+        "jgz +1 +1",
+        "out q",
+        "hlt",
+    ];
+    for line in &source {
+        let instruction: Instruction = line.parse().unwrap();
+        assert_eq!(&instruction.to_string(), line);
+    }
+    assert_eq!(
+        "set  c   7".parse::<Instruction>().unwrap(),
+        Instruction::Set(Reg::Named(2), Operand::Imm(7))
+    );
+}
+
+#[test]
+fn test_invalid() {
+    for line in &[
+        "",
+        "nop",
+        "nop a",
+        "acc +1 +2",
+        "jmp x",
+        "set 1 +1",
+        "set ab +1",
+        "mul a",
+        "out",
+        "hlt +0",
+        "jnz a b",
+        "xyz +1",
+    ] {
+        assert!(line.parse::<Instruction>().is_err(), "{}", line);
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+
+use super::instruction::{Instruction, Operand, Reg};
+
+/// Why a machine stopped running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    /// The program counter is just past the last instruction, or on a `hlt`
+    Terminated,
+    /// The machine was about to run the instruction at `pc` in a state it has been
+    /// in before, so it would never halt
+    LoopDetected { pc: usize, acc: i32 },
+    /// A jump from `from` to `pc`, before the start or past the end of the program
+    OutOfBounds { from: usize, pc: i64 },
+    /// The step limit ran out first
+    StepLimit,
+    /// The caller's `stop` returned true, leaving `pc` on the next instruction to run
+    Stopped,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Halt::Terminated => write!(f, "terminated"),
+            Halt::LoopDetected { pc, acc } => {
+                write!(f, "loops forever before {}, with acc {}", pc, acc)
+            }
+            Halt::OutOfBounds { from, pc } => {
+                write!(f, "jump from {} to {}, outside the program", from, pc)
+            }
+            Halt::StepLimit => write!(f, "step limit reached"),
+            Halt::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    pub program: Vec<Instruction>,
+    pub pc: usize,
+    pub acc: i32,
+    /// `a` to `z`
+    pub regs: [i32; 26],
+    /// Everything `out` has output so far
+    pub output: Vec<i32>,
+    /// Instructions run so far
+    pub steps: usize,
+}
+
+impl Machine {
+    pub fn new(program: Vec<Instruction>) -> Machine {
+        Machine {
+            program,
+            pc: 0,
+            acc: 0,
+            regs: [0; 26],
+            output: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn reg(&self, reg: Reg) -> i32 {
+        match reg {
+            Reg::Acc => self.acc,
+            Reg::Named(i) => self.regs[i as usize],
+        }
+    }
+
+    fn reg_mut(&mut self, reg: Reg) -> &mut i32 {
+        match reg {
+            Reg::Acc => &mut self.acc,
+            Reg::Named(i) => &mut self.regs[i as usize],
+        }
+    }
+
+    fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Reg(reg) => self.reg(reg),
+            Operand::Imm(val) => val,
+        }
+    }
+
+    /// Where a jump of `offset` from `pc` lands. Jumping to just past the last
+    /// instruction terminates the program, anywhere else outside it is an error.
+    fn jump(&self, offset: i32) -> Result<usize, Halt> {
+        let target = self.pc as i64 + offset as i64;
+        if (0..=self.program.len() as i64).contains(&target) {
+            Ok(target as usize)
+        } else {
+            Err(Halt::OutOfBounds {
+                from: self.pc,
+                pc: target,
+            })
+        }
+    }
+
+    /// Runs the instruction at `pc`. A jump out of the program fails without moving
+    /// `pc`, so the machine stays on the jump.
+    pub fn step(&mut self) -> Result<(), Halt> {
+        let instruction = match self.program.get(self.pc) {
+            Some(Instruction::Hlt) => return Err(Halt::Terminated),
+            Some(instruction) => *instruction,
+            None if self.pc == self.program.len() => return Err(Halt::Terminated),
+            None => {
+                return Err(Halt::OutOfBounds {
+                    from: self.pc,
+                    pc: self.pc as i64,
+                })
+            }
+        };
+        let mut next = self.pc + 1;
+        match instruction {
+            Instruction::Nop(_) | Instruction::Hlt => {}
+            Instruction::Acc(val) => self.acc = self.acc.wrapping_add(val),
+            Instruction::Jmp(offset) => next = self.jump(offset)?,
+            Instruction::Set(r, x) => *self.reg_mut(r) = self.value(x),
+            Instruction::Add(r, x) => {
+                let x = self.value(x);
+                let r = self.reg_mut(r);
+                *r = r.wrapping_add(x);
+            }
+            Instruction::Mul(r, x) => {
+                let x = self.value(x);
+                let r = self.reg_mut(r);
+                *r = r.wrapping_mul(x);
+            }
+            Instruction::Jz(x, offset) if self.value(x) == 0 => next = self.jump(offset)?,
+            Instruction::Jnz(x, offset) if self.value(x) != 0 => next = self.jump(offset)?,
+            Instruction::Jgz(x, offset) if self.value(x) > 0 => next = self.jump(offset)?,
+            Instruction::Jz(..) | Instruction::Jnz(..) | Instruction::Jgz(..) => {}
+            Instruction::Out(x) => self.output.push(self.value(x)),
+        }
+        self.pc = next;
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Whether every jump is unconditional, so the path through the program doesn't
+    /// depend on any values
+    fn static_control_flow(&self) -> bool {
+        self.program.iter().all(|instruction| {
+            !matches!(
+                instruction,
+                Instruction::Jz(..) | Instruction::Jnz(..) | Instruction::Jgz(..)
+            )
+        })
+    }
+
+    /// Runs until the machine halts, loops forever, `limit` more instructions have
+    /// run, or `stop` returns true for the machine as it is before an instruction.
+    ///
+    /// Without conditional jumps, any instruction about to run a second time means a
+    /// loop. Otherwise a loop is only certain once the whole state repeats, which is
+    /// found with Brent's algorithm, some time after the loop starts.
+    pub fn run_until(&mut self, limit: usize, mut stop: impl FnMut(&Machine) -> bool) -> Halt {
+        if self.static_control_flow() {
+            let mut ran = vec![false; self.program.len()];
+            for _ in 0..limit {
+                if stop(self) {
+                    return Halt::Stopped;
+                }
+                if let Some(ran) = ran.get_mut(self.pc) {
+                    if *ran {
+                        return Halt::LoopDetected {
+                            pc: self.pc,
+                            acc: self.acc,
+                        };
+                    }
+                    *ran = true;
+                }
+                if let Err(halt) = self.step() {
+                    return halt;
+                }
+            }
+        } else {
+            let state = |m: &Machine| (m.pc, m.acc, m.regs);
+            let mut saved = state(self);
+            let mut power = 1;
+            let mut since_saved = 0;
+            for _ in 0..limit {
+                if stop(self) {
+                    return Halt::Stopped;
+                }
+                if let Err(halt) = self.step() {
+                    return halt;
+                }
+                if state(self) == saved {
+                    return Halt::LoopDetected {
+                        pc: self.pc,
+                        acc: self.acc,
+                    };
+                }
+                since_saved += 1;
+                if since_saved == power {
+                    saved = state(self);
+                    power *= 2;
+                    since_saved = 0;
+                }
+            }
+        }
+        Halt::StepLimit
+    }
+
+    pub fn run(&mut self) -> Halt {
+        self.run_until(usize::MAX, |_| false)
+    }
+
+    /// Swaps the `nop` or `jmp` at `pc` for the other, keeping its operand. Returns
+    /// whether there was one to swap.
+    pub fn flip(&mut self, pc: usize) -> bool {
+        let flipped = match self.program.get(pc) {
+            Some(Instruction::Nop(val)) => Instruction::Jmp(*val),
+            Some(Instruction::Jmp(val)) => Instruction::Nop(*val),
+            _ => return false,
+        };
+        self.program[pc] = flipped;
+        true
+    }
+}
+
+#[cfg(test)]
+fn program(source: &str) -> Vec<Instruction> {
+    source.trim().lines().map(|l| l.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_step() {
+    use Instruction::*;
+    let mut machine = Machine::new(vec![Acc(3), Jmp(2), Acc(100), Nop(-7)]);
+    assert_eq!(machine.step(), Ok(()));
+    assert_eq!((machine.pc, machine.acc), (1, 3));
+    machine.step().unwrap();
+    assert_eq!(machine.pc, 3);
+    machine.step().unwrap();
+    assert_eq!(machine.step(), Err(Halt::Terminated));
+    assert_eq!((machine.pc, machine.acc, machine.steps), (4, 3, 3));
+}
+
+#[test]
+fn test_run_until() {
+    use Instruction::*;
+    let mut machine = Machine::new(vec![Acc(1), Acc(1), Acc(1)]);
+    assert_eq!(machine.run_until(2, |_| false), Halt::StepLimit);
+    assert_eq!((machine.pc, machine.acc), (2, 2));
+    assert_eq!(machine.run(), Halt::Terminated);
+    assert_eq!((machine.acc, machine.steps), (3, 3));
+
+    let mut machine = Machine::new(vec![Acc(1), Jmp(-1)]);
+    assert_eq!(machine.run(), Halt::LoopDetected { pc: 0, acc: 1 });
+    assert_eq!(machine.steps, 2);
+
+    let mut machine = Machine::new(vec![Acc(1), Acc(2), Acc(3)]);
+    assert_eq!(machine.run_until(10, |m| m.acc == 3), Halt::Stopped);
+    assert_eq!(machine.pc, 2);
+}
+
+#[test]
+fn test_jump_bounds() {
+    use Instruction::*;
+    let run = |program| {
+        let mut machine = Machine::new(program);
+        let halt = machine.run();
+        (halt, machine.pc, machine.steps)
+    };
+    assert_eq!(run(vec![]), (Halt::Terminated, 0, 0));
+    assert_eq!(run(vec![Nop(0), Acc(1)]), (Halt::Terminated, 2, 2));
+    assert_eq!(run(vec![Jmp(2), Acc(1)]), (Halt::Terminated, 2, 1));
+    assert_eq!(
+        run(vec![Jmp(3), Acc(1)]),
+        (Halt::OutOfBounds { from: 0, pc: 3 }, 0, 0)
+    );
+    assert_eq!(
+        run(vec![Nop(0), Jmp(-2)]),
+        (Halt::OutOfBounds { from: 1, pc: -1 }, 1, 1)
+    );
+    // Extreme offsets can't wrap around into the program
+    assert_eq!(
+        run(vec![Acc(1), Jmp(i32::MIN)]).0,
+        Halt::OutOfBounds {
+            from: 1,
+            pc: 1 + i32::MIN as i64
+        }
+    );
+    assert_eq!(
+        run(vec![Jmp(i32::MAX)]).0,
+        Halt::OutOfBounds {
+            from: 0,
+            pc: i32::MAX as i64
+        }
+    );
+}
+
+#[test]
+fn test_out_of_range_pc() {
+    let mut machine = Machine::new(vec![Instruction::Nop(0)]);
+    machine.pc = 4;
+    assert_eq!(machine.step(), Err(Halt::OutOfBounds { from: 4, pc: 4 }));
+}
+
+#[test]
+fn test_registers() {
+    // 5! into acc, counting down in a
+    let mut machine = Machine::new(program(
+        "
+set a +5
+set acc +1
+mul acc a
+add a -1
+out acc
+jnz a -3
+hlt
+acc +1000",
+    ));
+    assert_eq!(machine.run(), Halt::Terminated);
+    assert_eq!(
+        (machine.pc, machine.acc, machine.reg(Reg::Named(0))),
+        (6, 120, 0)
+    );
+    assert_eq!(machine.output, vec![5, 20, 60, 120, 120]);
+    assert_eq!(machine.step(), Err(Halt::Terminated));
+    assert_eq!(machine.steps, 2 + 5 * 4);
+}
+
+#[test]
+fn test_conditional_jumps() {
+    let run = |source| {
+        let mut machine = Machine::new(program(source));
+        machine.run();
+        machine.output
+    };
+    assert_eq!(run("jz +0 +2\nout +1\nout +2"), vec![2]);
+    assert_eq!(run("jz +3 +2\nout +1\nout +2"), vec![1, 2]);
+    assert_eq!(run("jnz b +2\nout +1\nout +2"), vec![1, 2]);
+    assert_eq!(run("set c -1\njgz c +2\nout +1\nout +2"), vec![1, 2]);
+    assert_eq!(run("set c +1\njgz c +2\nout +1\nout +2"), vec![2]);
+    assert_eq!(run("mul acc +7\nacc +6\nmul acc +7\nout acc"), vec![42]);
+}
+
+#[test]
+fn test_state_loops() {
+    // Revisits instructions without looping forever
+    let mut machine = Machine::new(program("set a +3\nadd a -1\njnz a -1"));
+    assert_eq!(machine.run(), Halt::Terminated);
+
+    // Flips between two states forever
+    let mut machine = Machine::new(program("mul a -1\njz +0 -1"));
+    assert!(matches!(machine.run(), Halt::LoopDetected { .. }));
+
+    // Every state is new, so only the limit stops it
+    let mut machine = Machine::new(program("add a +1\njgz +1 -1"));
+    assert_eq!(machine.run_until(1000, |_| false), Halt::StepLimit);
+    assert_eq!(machine.reg(Reg::Named(0)), 500);
+}
@@ -1,142 +1,136 @@
-use std::ops::Deref;
+mod analysis;
+mod asm;
+mod debug;
+mod flow;
+mod instruction;
+mod machine;
+mod repair;
+mod trace;
 
-fn main() -> anyhow::Result<()> {
-    let input: Vec<String> = input::read_lines("inputs/8")?;
-    let asm: Vec<Instruction> = parse_asm(&input);
+use std::io::Write;
 
-    println!("part 1: {}", run_asm_looping(&asm));
+use asm::{disassemble, parse_asm};
+use instruction::Instruction;
+use machine::{Halt, Machine};
+use repair::Repair;
 
-    println!("part 2: {}", run_asm_fix(&asm));
-    Ok(())
-}
+const USAGE: &str = "usage: day8
+       day8 run <file>
+       day8 disasm [file]
+       day8 debug [file]
+       day8 analyze [file]
+       day8 dot [file]
+       day8 repair [file]
+       day8 search <budget> [file]
+       day8 trace <file> <trace> [--fix]
+       day8 replay <trace>";
 
-#[derive(Debug, PartialEq, Eq)]
-enum Instruction {
-    Nop(i32),
-    Acc(i32),
-    Jmp(i32),
+fn read_program(path: &str) -> anyhow::Result<Vec<Instruction>> {
+    let input: Vec<String> = input::read_lines(path)?;
+    parse_asm(&input).map_err(|e| anyhow::anyhow!("{}: {}", path, e))
 }
 
-fn run_asm_looping(code: &[Instruction]) -> i32 {
-    let mut ran = vec![false; code.len()];
-    let mut acc = 0;
-    let mut pointer = 0;
-    loop {
-        let ran = &mut ran[pointer];
-        if *ran {
-            break;
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        [] => {}
+        ["run", path] => {
+            let mut machine = Machine::new(read_program(path)?);
+            let halt = machine.run();
+            for val in &machine.output {
+                println!("{}", val);
+            }
+            println!(
+                "{} after {} steps, acc {}",
+                halt, machine.steps, machine.acc
+            );
+            return Ok(());
+        }
+        ["analyze"] | ["analyze", _] => {
+            let program = read_program(args.get(1).copied().unwrap_or("inputs/8"))?;
+            print!("{}", analysis::Analysis::new(&program));
+            return Ok(());
         }
-        *ran = true;
-        let ins = &code[pointer];
-        match *ins {
-            Instruction::Nop(_) => {}
-            Instruction::Acc(val) => acc += val,
-            Instruction::Jmp(val) => {
-                if val < 0 {
-                    pointer -= -val as usize
-                } else {
-                    pointer += val as usize
-                }
-                // Do not advance the pointer
-                continue;
+        ["dot"] | ["dot", _] => {
+            let program = read_program(args.get(1).copied().unwrap_or("inputs/8"))?;
+            analysis::Analysis::new(&program).dot(&mut std::io::stdout().lock())?;
+            return Ok(());
+        }
+        ["repair"] | ["repair", _] => {
+            let program = read_program(args.get(1).copied().unwrap_or("inputs/8"))?;
+            match repair::flip(&program) {
+                Some(repair) => println!("{}", repair),
+                None => println!("no single flip makes the program terminate"),
             }
-        };
-        pointer += 1;
-    }
-    acc
-}
-
-fn run_asm_fix(code: &[Instruction]) -> i32 {
-    let mut ran = vec![false; code.len()];
-    let mut acc = 0;
-    let mut pointer = 0;
-    loop {
-        let has_ran = &mut ran[pointer];
-        if *has_ran {
-            break;
+            return Ok(());
         }
-        *has_ran = true;
-        let ins = &code[pointer];
-        match *ins {
-            Instruction::Nop(val) => {
-                // This would create a single-instruction loop
-                if val != 0 {
-                    // Try to jump
-                    pointer = (pointer as isize + val as isize) as usize;
-                    if let Ok(result_acc) = try_finish_run(code, &ran, &pointer, &acc) {
-                        return result_acc;
-                    } else {
-                        // Backtrack the jump
-                        pointer = (pointer as isize - val as isize) as usize;
-                    }
-                }
+        ["search", budget] | ["search", budget, _] => {
+            let budget = budget.parse()?;
+            let program = read_program(args.get(2).copied().unwrap_or("inputs/8"))?;
+            let solutions = repair::search(&program, budget);
+            if solutions.is_empty() {
+                println!("no repair with at most {} edits", budget);
             }
-            Instruction::Acc(val) => acc += val,
-            Instruction::Jmp(val) => {
-                // Try to do nothing
-                if let Ok(result_acc) = try_finish_run(code, &ran, &(pointer + 1), &acc) {
-                    return result_acc;
-                } else {
-                    // Jump normally
-                    pointer = (pointer as isize + val as isize) as usize;
-                }
-                // Do not advance the pointer
-                continue;
+            for solution in solutions {
+                println!("{}", solution);
             }
-        };
-        pointer += 1;
-    }
-    acc
-}
-
-fn try_finish_run(
-    code: &[Instruction],
-    ran: &[bool],
-    pointer: &usize,
-    acc: &i32,
-) -> Result<i32, i32> {
-    let mut ran = Vec::from(ran);
-    let mut acc = *acc;
-    let mut pointer = *pointer;
-    loop {
-        if pointer >= code.len() {
-            return Ok(acc);
-        }
-        let ran = &mut ran[pointer];
-        if *ran {
-            return Err(acc);
+            return Ok(());
         }
-        *ran = true;
-        let ins = &code[pointer];
-        match *ins {
-            Instruction::Nop(_) => {}
-            Instruction::Acc(val) => acc += val,
-            Instruction::Jmp(val) => {
-                if val < 0 {
-                    pointer -= -val as usize
-                } else {
-                    pointer += val as usize
-                }
-                // Do not advance the pointer
-                continue;
+        ["trace", path, trace_path] | ["trace", path, trace_path, "--fix"] => {
+            let mut program = read_program(path)?;
+            if args.len() == 4 {
+                let repair = repair::flip(&program)
+                    .ok_or_else(|| anyhow::anyhow!("no single flip fixes the program"))?;
+                program[repair.pc] = repair.to;
+                println!("repaired {}", repair);
             }
-        };
-        pointer += 1;
+            let mut machine = Machine::new(program);
+            let mut out = std::io::BufWriter::new(std::fs::File::create(trace_path)?);
+            let halt = trace::record(&mut machine, &mut out)?;
+            out.flush()?;
+            println!("{}, {} steps recorded", halt, machine.steps);
+            return Ok(());
+        }
+        ["replay", trace_path] => {
+            let file = std::io::BufReader::new(std::fs::File::open(trace_path)?);
+            let trace =
+                trace::read(file).map_err(|e| anyhow::anyhow!("{}: {:#}", trace_path, e))?;
+            print!("{}", trace::Summary::new(&trace));
+            return Ok(());
+        }
+        ["disasm"] | ["disasm", _] => {
+            let program = read_program(args.get(1).copied().unwrap_or("inputs/8"))?;
+            print!("{}", disassemble(&program));
+            return Ok(());
+        }
+        ["debug"] | ["debug", _] => {
+            let program = read_program(args.get(1).copied().unwrap_or("inputs/8"))?;
+            let mut debugger = debug::Debugger::new(Machine::new(program));
+            debugger.run(std::io::stdin().lock(), &mut std::io::stdout())?;
+            return Ok(());
+        }
+        _ => anyhow::bail!(USAGE),
     }
+
+    let asm = read_program("inputs/8")?;
+
+    let looping =
+        run_asm_looping(&asm).ok_or_else(|| anyhow::anyhow!("the program doesn't loop"))?;
+    println!("part 1: {}", looping);
+
+    let Repair { acc: fixed, .. } =
+        repair::flip(&asm).ok_or_else(|| anyhow::anyhow!("no single flip fixes the program"))?;
+    println!("part 2: {}", fixed);
+    Ok(())
 }
 
-fn parse_asm<T: Deref<Target = str>>(input: &[T]) -> Vec<Instruction> {
-    let mut out = Vec::with_capacity(input.len());
-    for line in input {
-        let val = line[4..].parse::<i32>().unwrap();
-        match &line[0..3] {
-            "nop" => out.push(Instruction::Nop(val)),
-            "acc" => out.push(Instruction::Acc(val)),
-            "jmp" => out.push(Instruction::Jmp(val)),
-            _ => (),
-        }
+/// The accumulator just before any instruction runs a second time
+fn run_asm_looping(code: &[Instruction]) -> Option<i32> {
+    match Machine::new(code.to_vec()).run() {
+        Halt::LoopDetected { acc, .. } => Some(acc),
+        _ => None,
     }
-    out
 }
 
 #[cfg(test)]
@@ -152,26 +146,29 @@ jmp -4
 acc +6
 ";
 
+#[cfg(test)]
+fn test_program() -> Vec<Instruction> {
+    let lines: Vec<&str> = TEST_INPUT.trim().lines().collect();
+    parse_asm(&lines).unwrap()
+}
+
 #[test]
 fn test_run_asm_looping() {
-    let lines: Vec<String> = TEST_INPUT
-        .trim()
-        .lines()
-        .map(|l| l.trim().to_string())
-        .collect();
-    let asm: Vec<Instruction> = parse_asm(&lines);
+    let asm = test_program();
     let result = run_asm_looping(&asm);
-    assert_eq!(result, 5);
+    assert_eq!(result, Some(5));
 }
 
 #[test]
 fn test_run_asm_fix() {
-    let lines: Vec<String> = TEST_INPUT
-        .trim()
-        .lines()
-        .map(|l| l.trim().to_string())
-        .collect();
-    let asm: Vec<Instruction> = parse_asm(&lines);
-    let result = run_asm_fix(&asm);
-    assert_eq!(result, 8);
+    let asm = test_program();
+    let result = repair::flip(&asm).map(|repair| repair.acc);
+    assert_eq!(result, Some(8));
+}
+
+#[test]
+fn test_no_loop() {
+    use Instruction::*;
+    assert_eq!(run_asm_looping(&[Acc(1), Jmp(1)]), None);
+    assert_eq!(run_asm_looping(&[Jmp(-1)]), None);
 }
@@ -0,0 +1,457 @@
+//! Changing a program that never terminates so that it does
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+use super::{
+    flow,
+    instruction::Instruction,
+    machine::{Halt, Machine},
+};
+
+/// How many instructions a repaired program may run before it counts as looping
+/// forever, for programs whose loops can't always be detected
+const STEP_LIMIT: usize = 1_000_000;
+
+/// One instruction replaced, and the accumulator the program then terminates with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    pub pc: usize,
+    pub from: Instruction,
+    pub to: Instruction,
+    pub acc: i32,
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: `{}` -> `{}`, acc {}",
+            self.pc, self.from, self.to, self.acc
+        )
+    }
+}
+
+fn flipped(instruction: Instruction) -> Option<Instruction> {
+    match instruction {
+        Instruction::Nop(val) => Some(Instruction::Jmp(val)),
+        Instruction::Jmp(val) => Some(Instruction::Nop(val)),
+        _ => None,
+    }
+}
+
+/// Finds the `nop` or `jmp` to swap for the other so the program terminates, in time
+/// linear in its length.
+///
+/// The unchanged program runs until it repeats an instruction, and only the
+/// instructions it runs on the way can matter. Flipping one of them helps when the
+/// flipped instruction leads somewhere that reaches termination in the control-flow
+/// graph. None of the instructions run can reach termination themselves, so that
+/// path never comes back through the flip, and the first one found is the answer.
+/// With conditional jumps the graph only says termination may be reachable, so each
+/// candidate is run to check.
+pub fn flip(program: &[Instruction]) -> Option<Repair> {
+    let mut first = None;
+    flips_while(program, usize::MAX, |repair| {
+        first = Some(repair);
+        false
+    });
+    first
+}
+
+/// Every flip of an instruction the program runs after which it terminates, in the
+/// order they're run. Unlike `flip`, which stops at the first, this tries them all,
+/// so a flipped program running more than `STEP_LIMIT` instructions counts as never
+/// terminating.
+pub fn flips(program: &[Instruction]) -> Vec<Repair> {
+    let mut all = Vec::new();
+    flips_while(program, STEP_LIMIT, |repair| {
+        all.push(repair);
+        true
+    });
+    all
+}
+
+/// Passes each flip that terminates within `limit` instructions to `found`, the way
+/// `flip` finds them, until it returns false
+fn flips_while(program: &[Instruction], limit: usize, mut found: impl FnMut(Repair) -> bool) {
+    let reaches = flow::reaches_end(program);
+    let mut machine = Machine::new(program.to_vec());
+    let mut ran = vec![false; program.len()];
+    while let Some(ran) = ran.get_mut(machine.pc) {
+        if *ran {
+            break;
+        }
+        *ran = true;
+        let (pc, from) = (machine.pc, program[machine.pc]);
+        if let Some(to) = flipped(from) {
+            let next = flow::successors(to, pc, program.len());
+            if next.iter().any(|&next| reaches[next]) {
+                let mut patched = machine.clone();
+                patched.program[pc] = to;
+                if patched.run_until(limit, |_| false) == Halt::Terminated {
+                    let repair = Repair {
+                        pc,
+                        from,
+                        to,
+                        acc: patched.acc,
+                    };
+                    if !found(repair) {
+                        return;
+                    }
+                }
+            }
+        }
+        if machine.step().is_err() {
+            break;
+        }
+    }
+}
+
+/// A change to one instruction, by its address in the original program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edit {
+    /// Swaps a `nop` or `jmp` for the other
+    Flip(usize),
+    /// Gives a jump a new offset
+    Retarget(usize, i32),
+    /// Removes the instruction, moving the ones after it up
+    Delete(usize),
+}
+
+impl Edit {
+    pub fn pc(&self) -> usize {
+        match self {
+            Edit::Flip(pc) | Edit::Retarget(pc, _) | Edit::Delete(pc) => *pc,
+        }
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Flip(pc) => write!(f, "flip {}", pc),
+            Edit::Retarget(pc, offset) => write!(f, "jump at {} by {:+}", pc, offset),
+            Edit::Delete(pc) => write!(f, "delete {}", pc),
+        }
+    }
+}
+
+/// A set of edits that makes a program terminate, and the accumulator it ends with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub edits: Vec<Edit>,
+    pub acc: i32,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.edits.is_empty() {
+            write!(f, "no edits")?;
+        }
+        for (i, edit) in self.edits.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{}{}", sep, edit)?;
+        }
+        write!(f, ": acc {}", self.acc)
+    }
+}
+
+fn with_offset(instruction: Instruction, offset: i32) -> Option<Instruction> {
+    match instruction {
+        Instruction::Jmp(_) => Some(Instruction::Jmp(offset)),
+        Instruction::Jz(x, _) => Some(Instruction::Jz(x, offset)),
+        Instruction::Jnz(x, _) => Some(Instruction::Jnz(x, offset)),
+        Instruction::Jgz(x, _) => Some(Instruction::Jgz(x, offset)),
+        _ => None,
+    }
+}
+
+/// The program with `edits` made, and the original address of each instruction left
+pub fn apply(program: &[Instruction], edits: &[Edit]) -> (Vec<Instruction>, Vec<usize>) {
+    let mut edited = program.to_vec();
+    let mut deleted = vec![false; program.len()];
+    for edit in edits {
+        match *edit {
+            Edit::Flip(pc) => edited[pc] = flipped(edited[pc]).unwrap_or(edited[pc]),
+            Edit::Retarget(pc, offset) => {
+                edited[pc] = match edited[pc] {
+                    // Keeps the offset for a flip, whichever order they're made in
+                    Instruction::Nop(_) => Instruction::Nop(offset),
+                    other => with_offset(other, offset).unwrap_or(other),
+                }
+            }
+            Edit::Delete(pc) => deleted[pc] = true,
+        }
+    }
+    let kept: Vec<usize> = (0..program.len()).filter(|&pc| !deleted[pc]).collect();
+    (kept.iter().map(|&pc| edited[pc]).collect(), kept)
+}
+
+/// Finds every smallest set of at most `budget` edits that makes the program
+/// terminate, with the accumulator each ends with. Returns a single empty set if the
+/// program terminates already, and nothing if no set within budget works.
+///
+/// Only edits that can change what the program does are tried: flips and new jump
+/// offsets for instructions the program runs with the edits so far, and deleting any
+/// instruction, since that moves the targets of jumps across it. A last edit is only
+/// tried if where it leads can reach termination in the control-flow graph. An
+/// instruction gets at most one edit, except that a `nop` can be flipped and given a
+/// new offset, to become a `jmp` anywhere. Each set of edits is only tried once,
+/// whatever order it's reached in.
+pub fn search(program: &[Instruction], budget: usize) -> Vec<Solution> {
+    for size in 0..=budget {
+        let mut found = BTreeMap::new();
+        extend(
+            program,
+            &mut Vec::new(),
+            size,
+            &mut HashSet::new(),
+            &mut found,
+        );
+        if !found.is_empty() {
+            return found
+                .into_iter()
+                .map(|(edits, acc)| Solution { edits, acc })
+                .collect();
+        }
+    }
+    Vec::new()
+}
+
+/// `edits` in a fixed order, so the same set is always the same
+fn sorted(edits: &[Edit]) -> Vec<Edit> {
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|edit| (edit.pc(), *edit));
+    edits
+}
+
+/// Tries every way to add `left` more edits to `edits`, keeping those that terminate.
+/// Sets of edits already in `explored` are skipped.
+fn extend(
+    program: &[Instruction],
+    edits: &mut Vec<Edit>,
+    left: usize,
+    explored: &mut HashSet<Vec<Edit>>,
+    found: &mut BTreeMap<Vec<Edit>, i32>,
+) {
+    let (edited, original) = apply(program, edits);
+    let mut machine = Machine::new(edited.clone());
+    let mut ran = vec![false; edited.len()];
+    let halt = machine.run_until(STEP_LIMIT, |m| {
+        if let Some(ran) = ran.get_mut(m.pc) {
+            *ran = true;
+        }
+        false
+    });
+    if halt == Halt::Terminated {
+        // Anything with more edits isn't a smallest repair
+        if left == 0 {
+            found.insert(sorted(edits), machine.acc);
+        }
+        return;
+    }
+    if left == 0 {
+        return;
+    }
+
+    let len = edited.len();
+    let reaches = flow::reaches_end(&edited);
+    let leads_to_end = |instruction, pc| {
+        left > 1
+            || flow::successors(instruction, pc, len)
+                .iter()
+                .any(|&next| reaches[next])
+    };
+    let mut candidates = Vec::new();
+    for (pc, &instruction) in edited.iter().enumerate() {
+        if ran[pc] {
+            if let Some(to) = flipped(instruction) {
+                if leads_to_end(to, pc) {
+                    candidates.push(Edit::Flip(original[pc]));
+                }
+            }
+            let current = flow::successors(instruction, pc, len);
+            for target in 0..=len {
+                let offset = (target as i64 - pc as i64) as i32;
+                let to = match with_offset(instruction, offset) {
+                    Some(to) => to,
+                    None => break,
+                };
+                // A jmp to the next instruction is the same as a flip
+                let same_as_flip = matches!(instruction, Instruction::Jmp(_)) && target == pc + 1;
+                if !current.contains(&target) && !same_as_flip && leads_to_end(to, pc) {
+                    candidates.push(Edit::Retarget(original[pc], offset));
+                }
+            }
+        }
+        candidates.push(Edit::Delete(original[pc]));
+    }
+
+    for candidate in candidates {
+        let clashes = edits.iter().any(|edit| {
+            edit.pc() == candidate.pc()
+                && !matches!(
+                    (edit, candidate),
+                    (Edit::Flip(_), Edit::Retarget(..)) | (Edit::Retarget(..), Edit::Flip(_))
+                )
+        });
+        if clashes {
+            continue;
+        }
+        edits.push(candidate);
+        if explored.insert(sorted(edits)) {
+            extend(program, edits, left - 1, explored, found);
+        }
+        edits.pop();
+    }
+}
+
+#[test]
+fn test_flip() {
+    let program = super::test_program();
+    let repair = flip(&program).unwrap();
+    assert_eq!(
+        repair,
+        Repair {
+            pc: 7,
+            from: Instruction::Jmp(-4),
+            to: Instruction::Nop(-4),
+            acc: 8,
+        }
+    );
+    assert_eq!(repair.to_string(), "7: `jmp -4` -> `nop -4`, acc 8");
+}
+
+#[test]
+fn test_flip_edge_cases() {
+    use Instruction::*;
+    assert_eq!(flip(&[Jmp(-1)]).map(|r| (r.pc, r.acc)), Some((0, 0)));
+    assert_eq!(flip(&[Acc(1), Jmp(-1), Jmp(-2)]), None);
+    // Already terminates
+    assert_eq!(flip(&[Nop(0), Acc(1)]), None);
+    // Flipping 0 or 1 leads into the jmp +0 instead
+    assert_eq!(
+        flip(&[Nop(2), Jmp(2), Jmp(0), Jmp(-3), Acc(1)]).map(|r| (r.pc, r.acc)),
+        Some((3, 1))
+    );
+}
+
+#[test]
+fn test_flip_long_run() {
+    use super::instruction::{Operand, Reg::Named};
+    use Instruction::*;
+    // Flipping 0 skips the loop at 1 for a countdown of over a million steps
+    let program = [
+        Nop(2),
+        Jmp(0),
+        Set(Named(0), Operand::Imm(600_000)),
+        Add(Named(0), Operand::Imm(-1)),
+        Jnz(Operand::Reg(Named(0)), -1),
+    ];
+    assert_eq!(flip(&program).map(|r| r.pc), Some(0));
+    assert_eq!(flips(&program), vec![]);
+}
+
+#[test]
+fn test_flip_conditional() {
+    use super::instruction::{Operand::Imm, Reg::Named};
+    use Instruction::*;
+    // The graph says the jnz may fall through to the end, but it never does
+    let program = [Set(Named(0), Imm(1)), Jmp(1), Jnz(Imm(1), -1), Nop(-1)];
+    assert_eq!(flip(&program), None);
+}
+
+#[test]
+fn test_apply() {
+    use Instruction::*;
+    let program = [Nop(1), Acc(2), Jmp(-1), Acc(4)];
+    assert_eq!(
+        apply(
+            &program,
+            &[Edit::Flip(0), Edit::Retarget(2, 3), Edit::Delete(1)]
+        ),
+        (vec![Jmp(1), Jmp(3), Acc(4)], vec![0, 2, 3])
+    );
+    assert_eq!(apply(&program, &[]), (program.to_vec(), vec![0, 1, 2, 3]));
+    // Flipping and retargeting a nop makes a jmp, in either order
+    for edits in [
+        [Edit::Flip(0), Edit::Retarget(0, 3)],
+        [Edit::Retarget(0, 3), Edit::Flip(0)],
+    ] {
+        assert_eq!(apply(&program, &edits).0[0], Jmp(3));
+    }
+}
+
+#[test]
+fn test_search() {
+    let program = super::test_program();
+    let found: Vec<String> = search(&program, 3)
+        .iter()
+        .map(|solution| solution.to_string())
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            "flip 7: acc 8",
+            "jump at 2 by +6: acc 7",
+            "jump at 2 by +7: acc 1",
+            "jump at 4 by +4: acc 11",
+            "jump at 4 by +5: acc 5",
+            "jump at 7 by +2: acc 2",
+            "delete 7: acc 8",
+        ]
+    );
+    assert_eq!(search(&program, 0), vec![]);
+}
+
+#[test]
+fn test_search_sizes() {
+    use Instruction::*;
+    assert_eq!(
+        search(&[Acc(1)], 2),
+        vec![Solution {
+            edits: vec![],
+            acc: 1
+        }]
+    );
+    // Out of bounds rather than looping
+    assert_eq!(
+        search(&[Jmp(5), Acc(1)], 1),
+        vec![
+            Solution {
+                edits: vec![Edit::Flip(0)],
+                acc: 1
+            },
+            Solution {
+                edits: vec![Edit::Retarget(0, 2)],
+                acc: 0
+            },
+            Solution {
+                edits: vec![Edit::Delete(0)],
+                acc: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_flip_and_retarget() {
+    use Instruction::*;
+    // Flipping the nop alone jumps out of the program
+    let program = [Nop(5), Jmp(0)];
+    let mut found = BTreeMap::new();
+    extend(
+        &program,
+        &mut Vec::new(),
+        2,
+        &mut HashSet::new(),
+        &mut found,
+    );
+    assert_eq!(
+        found.get(&vec![Edit::Flip(0), Edit::Retarget(0, 2)]),
+        Some(&0)
+    );
+}
@@ -0,0 +1,268 @@
+//! Execution traces, one line per instruction run:
+//!
+//! ```text
+//! <pc> <instruction> <acc before> <acc after>
+//! ```
+//!
+//! separated by tabs. Lines starting with `#` are comments.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use anyhow::{anyhow, Context};
+
+use super::{
+    instruction::Instruction,
+    machine::{Halt, Machine},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub acc_before: i32,
+    pub acc_after: i32,
+}
+
+pub fn write<W: Write>(trace: &[Entry], out: &mut W) -> io::Result<()> {
+    for entry in trace {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            entry.pc, entry.instruction, entry.acc_before, entry.acc_after
+        )?;
+    }
+    Ok(())
+}
+
+pub fn read<R: BufRead>(input: R) -> anyhow::Result<Vec<Entry>> {
+    let mut trace = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = || -> anyhow::Result<Entry> {
+            match line.split('\t').collect::<Vec<_>>()[..] {
+                [pc, instruction, before, after] => Ok(Entry {
+                    pc: pc.parse()?,
+                    instruction: instruction.parse()?,
+                    acc_before: before.parse()?,
+                    acc_after: after.parse()?,
+                }),
+                _ => Err(anyhow!("expected 4 fields separated by tabs")),
+            }
+        };
+        trace.push(entry().with_context(|| format!("line {}", i + 1))?);
+    }
+    Ok(trace)
+}
+
+/// The machine's step count, pc and acc the last time it was looked at
+type Seen = (usize, usize, i32);
+
+/// Writes the instruction at `seen` if the machine has run it since
+fn write_ran<W: Write>(m: &Machine, seen: &mut Seen, out: &mut W) -> io::Result<()> {
+    let (steps, pc, acc) = *seen;
+    if m.steps > steps {
+        let entry = Entry {
+            pc,
+            instruction: m.program[pc],
+            acc_before: acc,
+            acc_after: m.acc,
+        };
+        write(&[entry], out)?;
+    }
+    *seen = (m.steps, m.pc, m.acc);
+    Ok(())
+}
+
+/// Runs the machine like `Machine::run`, writing each instruction to `out` as soon as
+/// it has run. If the machine loops forever, the instruction about to run a second
+/// time is run and written too, so the trace shows the loop closing.
+pub fn record<W: Write>(machine: &mut Machine, out: &mut W) -> io::Result<Halt> {
+    let mut seen = (machine.steps, machine.pc, machine.acc);
+    let mut result = Ok(());
+    let halt = machine.run_until(usize::MAX, |m| {
+        result = write_ran(m, &mut seen, out);
+        result.is_err()
+    });
+    result?;
+    write_ran(machine, &mut seen, out)?;
+    if let Halt::LoopDetected { .. } = halt {
+        machine.step().ok();
+        write_ran(machine, &mut seen, out)?;
+    }
+    Ok(halt)
+}
+
+/// The first time an instruction runs again. Steps count from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repeat {
+    pub pc: usize,
+    /// The step that first ran it, where the loop starts
+    pub first: usize,
+    /// The step that ran it again
+    pub again: usize,
+    /// The instruction before it runs again, which closes the loop
+    pub back_from: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub steps: usize,
+    pub acc: Option<(i32, i32)>,
+    /// How many times each instruction ran, by pc
+    pub hits: BTreeMap<usize, (usize, Instruction)>,
+    pub first_repeat: Option<Repeat>,
+}
+
+impl Summary {
+    pub fn new(trace: &[Entry]) -> Summary {
+        let mut hits: BTreeMap<usize, (usize, Instruction)> = BTreeMap::new();
+        let mut first_run = BTreeMap::new();
+        let mut first_repeat = None;
+        for (step, entry) in trace.iter().enumerate() {
+            hits.entry(entry.pc).or_insert((0, entry.instruction)).0 += 1;
+            match first_run.get(&entry.pc) {
+                Some(first) if first_repeat.is_none() => {
+                    first_repeat = Some(Repeat {
+                        pc: entry.pc,
+                        first: first + 1,
+                        again: step + 1,
+                        back_from: trace[step - 1].pc,
+                    })
+                }
+                Some(_) => {}
+                None => {
+                    first_run.insert(entry.pc, step);
+                }
+            }
+        }
+        Summary {
+            steps: trace.len(),
+            acc: trace
+                .first()
+                .zip(trace.last())
+                .map(|(first, last)| (first.acc_before, last.acc_after)),
+            hits,
+            first_repeat,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} steps", self.steps)?;
+        if let Some((before, after)) = self.acc {
+            write!(f, ", acc {} -> {}", before, after)?;
+        }
+        writeln!(f)?;
+        match self.first_repeat {
+            Some(repeat) => {
+                let (_, instruction) = self.hits[&repeat.pc];
+                writeln!(
+                    f,
+                    "first instruction run twice: {} `{}`, at steps {} and {}",
+                    repeat.pc, instruction, repeat.first, repeat.again
+                )?;
+                let (_, jump) = self.hits[&repeat.back_from];
+                writeln!(
+                    f,
+                    "loop entered at {} on step {}, closed by {} `{}`",
+                    repeat.pc, repeat.first, repeat.back_from, jump
+                )?;
+            }
+            None => writeln!(f, "no instruction ran twice")?,
+        }
+        writeln!(f, "   pc   hits  instruction")?;
+        for (pc, (hits, instruction)) in &self.hits {
+            writeln!(f, "{:>5}  {:>5}  {}", pc, hits, instruction)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_trace() -> Vec<Entry> {
+    let mut machine = Machine::new(super::test_program());
+    let mut out = Vec::new();
+    assert!(matches!(
+        record(&mut machine, &mut out).unwrap(),
+        Halt::LoopDetected { pc: 1, acc: 5 }
+    ));
+    read(&out[..]).unwrap()
+}
+
+#[test]
+fn test_write_and_read() {
+    let trace = test_trace();
+    let mut out = Vec::new();
+    write(&trace, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("0\tnop +0\t0\t0\n1\tacc +1\t0\t1\n2\tjmp +4\t1\t1\n"));
+    assert_eq!(read(text.as_bytes()).unwrap(), trace);
+    assert_eq!(
+        read("# comment\n\n3\thlt\t5\t5".as_bytes()).unwrap().len(),
+        1
+    );
+
+    let err = read("0\tnop +0\t0\t0\n1\tacc +1\t0".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 2");
+    assert!(read("x\tnop +0\t0\t0".as_bytes()).is_err());
+}
+
+#[test]
+fn test_summary() {
+    let summary = Summary::new(&test_trace());
+    assert_eq!(summary.steps, 8);
+    assert_eq!(summary.acc, Some((0, 6)));
+    assert_eq!(
+        summary.first_repeat,
+        Some(Repeat {
+            pc: 1,
+            first: 2,
+            again: 8,
+            back_from: 4,
+        })
+    );
+    assert_eq!(summary.hits[&1], (2, Instruction::Acc(1)));
+    assert_eq!(summary.hits.len(), 7);
+    let text = summary.to_string();
+    assert!(text.starts_with(
+        "8 steps, acc 0 -> 6
+first instruction run twice: 1 `acc +1`, at steps 2 and 8
+loop entered at 1 on step 2, closed by 4 `jmp -3`
+   pc   hits  instruction
+    0      1  nop +0
+    1      2  acc +1
+"
+    ));
+
+    let summary = Summary::new(&[]);
+    assert_eq!(
+        summary.to_string(),
+        "0 steps\nno instruction ran twice\n   pc   hits  instruction\n"
+    );
+}
+
+#[test]
+fn test_record() {
+    use Instruction::*;
+    let mut machine = Machine::new(vec![Acc(2), Jmp(2), Acc(100), Hlt]);
+    let mut out = Vec::new();
+    assert_eq!(record(&mut machine, &mut out).unwrap(), Halt::Terminated);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "0\tacc +2\t0\t2\n1\tjmp +2\t2\t2\n"
+    );
+
+    // A jump out of the program isn't run
+    let mut machine = Machine::new(vec![Acc(1), Jmp(-5)]);
+    let mut out = Vec::new();
+    record(&mut machine, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "0\tacc +1\t0\t1\n");
+}
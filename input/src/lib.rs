@@ -1,8 +1,13 @@
 use anyhow::Result;
-use std::{fs::File, io::Read, path::Path, str::FromStr};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
 
 pub fn read_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
-    let file = File::open(path)?;
+    let file = BufReader::new(File::open(path)?);
     Ok(file.bytes().map(|b| b.unwrap()).collect())
 }
 